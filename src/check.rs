@@ -0,0 +1,241 @@
+use crate::config::is_settings_key;
+use crate::error::Error;
+use crate::hosts::Host;
+use crate::network::{InterfaceNetwork, NetworkProvider};
+use itertools::Itertools;
+use serde_yaml::Value;
+use std::collections::HashMap;
+use std::fmt;
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::scanner::Marker;
+
+/// Where a value is in a config. Entries of mappings and sequences are
+/// tracked by their position, which is what ties a `Value` back to the
+/// line and column it was parsed from.
+#[derive(Clone, Debug, Default)]
+pub struct YamlPath {
+    indexes: Vec<usize>,
+    names: Vec<String>,
+}
+
+impl YamlPath {
+    /// The path of entry `i` of a mapping, which has the key `key`.
+    pub fn key(&self, i: usize, key: &Value) -> Self {
+        let name = match key {
+            Value::String(s) if !s.is_empty() && !s.contains(&['.', '['][..]) => s.clone(),
+            _ => serde_json::to_string(key).unwrap_or_default(),
+        };
+        self.join(i, name)
+    }
+
+    /// The path of item `i` of a sequence.
+    pub fn index(&self, i: usize) -> Self {
+        self.join(i, format!("[{}]", i))
+    }
+
+    fn join(&self, i: usize, name: String) -> Self {
+        let mut path = self.clone();
+        path.indexes.push(i);
+        path.names.push(name);
+        path
+    }
+}
+
+impl fmt::Display for YamlPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, name) in self.names.iter().enumerate() {
+            if i > 0 && !name.starts_with('[') {
+                write!(f, ".")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
+
+/// An error found in a config, with where it is.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub path: YamlPath,
+    /// Line and column, both starting at 1, when the text of the config is
+    /// known.
+    pub location: Option<(usize, usize)>,
+    pub error: Error,
+}
+
+impl Diagnostic {
+    pub fn new(path: YamlPath, error: Error) -> Self {
+        Self {
+            path,
+            location: None,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((line, col)) = self.location {
+            write!(f, "{}:{}: ", line, col)?;
+        }
+        if !self.path.names.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+/// Checks a config for errors, including selectors that match none of the
+/// networks of `provider`.
+pub fn check_config(text: &str, provider: &dyn NetworkProvider) -> Vec<Diagnostic> {
+    let val: Value = match serde_yaml::from_str(text) {
+        Ok(val) => val,
+        Err(e) => {
+            return vec![Diagnostic {
+                path: YamlPath::default(),
+                location: e.location().map(|l| (l.line(), l.column())),
+                error: e.into(),
+            }]
+        }
+    };
+    let positions = Positions::parse(text);
+    let mut found = Vec::new();
+    check_val(&val, &provider.networks(), &mut found);
+    for d in &mut found {
+        d.location = positions.location(&d.path);
+    }
+    found
+}
+
+/// Finds what `entries_from_val` would warn about or skip in `val`.
+pub fn check_val(val: &Value, networks: &[InterfaceNetwork], found: &mut Vec<Diagnostic>) {
+    let root = YamlPath::default();
+    match val {
+        Value::Sequence(seq) => {
+            for (i, v) in seq.iter().enumerate() {
+                check_map(v, &root.index(i), networks, found);
+            }
+        }
+        _ => check_map(val, &root, networks, found),
+    }
+}
+
+fn check_map(
+    val: &Value,
+    path: &YamlPath,
+    networks: &[InterfaceNetwork],
+    found: &mut Vec<Diagnostic>,
+) {
+    let map = match val {
+        Value::Mapping(map) => map,
+        _ => {
+            found.push(Diagnostic::new(
+                path.clone(),
+                Error::InvalidEntryMap(val.clone()),
+            ));
+            return;
+        }
+    };
+    for (i, (k, v)) in map.iter().enumerate() {
+        let path = path.key(i, k);
+        if is_settings_key(k) {
+            if !InterfaceNetwork::filter_networks(networks, k).is_empty() {
+                found.push(Diagnostic::new(path, Error::ShadowedSelector(k.clone())));
+            }
+            continue;
+        }
+        let nets = InterfaceNetwork::filter_networks(networks, k);
+        match InterfaceNetwork::check_selector(k) {
+            Err(e) => found.push(Diagnostic::new(path.clone(), e)),
+            Ok(()) if nets.is_empty() => found.push(Diagnostic::new(
+                path.clone(),
+                Error::UnmatchedSelector(k.clone()),
+            )),
+            Ok(()) => {}
+        }
+        Host::check_hosts(v, &path, found);
+        for host in Host::parse_hosts(v.clone(), path.clone()).filter_map(Result::ok) {
+            let errors = nets.iter().filter_map(|net| host.check_secret(net).err());
+            found.extend(
+                errors
+                    .map(|e| Diagnostic::new(path.clone(), e))
+                    .unique_by(Diagnostic::to_string),
+            );
+        }
+    }
+}
+
+enum Frame {
+    Sequence(Vec<usize>, usize),
+    // path, index of the current entry, and whether its key is next
+    Mapping(Vec<usize>, usize, bool),
+    // inside a mapping key that is itself a mapping or sequence
+    Key,
+}
+
+/// Where each value of a yaml document starts, found by replaying the
+/// parser events. A mapping entry is located at its key.
+#[derive(Default)]
+struct Positions {
+    marks: HashMap<Vec<usize>, Marker>,
+    stack: Vec<Frame>,
+}
+
+impl Positions {
+    fn parse(text: &str) -> Self {
+        let mut positions = Self::default();
+        // syntax errors are already reported by serde_yaml
+        let _ = Parser::new(text.chars()).load(&mut positions, false);
+        positions
+    }
+
+    fn location(&self, path: &YamlPath) -> Option<(usize, usize)> {
+        self.marks
+            .get(&path.indexes)
+            .map(|m| (m.line(), m.col() + 1))
+    }
+}
+
+impl MarkedEventReceiver for Positions {
+    fn on_event(&mut self, ev: Event, mark: Marker) {
+        let container = match ev {
+            Event::SequenceStart(_) | Event::MappingStart(_) => true,
+            Event::Scalar(..) | Event::Alias(_) => false,
+            Event::SequenceEnd | Event::MappingEnd => {
+                self.stack.pop();
+                return;
+            }
+            _ => return,
+        };
+        let (path, in_key) = match self.stack.last_mut() {
+            None => (Some(vec![]), false),
+            Some(Frame::Key) => (None, true),
+            Some(Frame::Sequence(path, next)) => {
+                let mut path = path.clone();
+                path.push(*next);
+                *next += 1;
+                (Some(path), false)
+            }
+            Some(Frame::Mapping(path, next, is_key)) => {
+                let mut path = path.clone();
+                path.push(*next);
+                let in_key = *is_key;
+                if !in_key {
+                    *next += 1;
+                }
+                *is_key = !in_key;
+                (Some(path), in_key)
+            }
+        };
+        if let Some(path) = &path {
+            self.marks.entry(path.clone()).or_insert(mark);
+        }
+        if container {
+            self.stack.push(match (path, ev) {
+                (Some(path), Event::SequenceStart(_)) if !in_key => Frame::Sequence(path, 0),
+                (Some(path), _) if !in_key => Frame::Mapping(path, 0, true),
+                _ => Frame::Key,
+            });
+        }
+    }
+}
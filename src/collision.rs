@@ -0,0 +1,336 @@
+use crate::entry::{Entry, Source};
+use log::warn;
+use pnet::datalink::MacAddr;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// What to do when distinct hosts end up with the same ip or mac address.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CollisionPolicy {
+    Error,
+    Warn,
+    FirstWins,
+    LastWins,
+}
+
+pub const COLLISION_POLICIES: &[&str] = &["error", "warn", "first-wins", "last-wins"];
+
+impl FromStr for CollisionPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "error" => Ok(Self::Error),
+            "warn" => Ok(Self::Warn),
+            "first-wins" => Ok(Self::FirstWins),
+            "last-wins" => Ok(Self::LastWins),
+            _ => Err(format!("unknown collision policy: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Address {
+    Ip(IpAddr),
+    Mac(MacAddr),
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Ip(ip) => write!(f, "ip {}", ip),
+            Self::Mac(mac) => write!(f, "mac {}", mac),
+        }
+    }
+}
+
+/// Two entries of distinct hosts with the same address. `first` is the one
+/// that came first in the configs and leases files.
+pub struct Collision {
+    pub address: Address,
+    pub first: Entry,
+    pub second: Entry,
+}
+
+fn describe(e: &Entry) -> String {
+    let mut s = e.name.clone();
+    match &e.source {
+        Some(Source::Config(file)) | Some(Source::Leases(file)) => s += &format!(" from {}", file),
+        None => {}
+    }
+    if let Some(net) = &e.network {
+        let iface = net.iface.as_ref().map(|i| i.name.as_str()).unwrap_or("-");
+        s += &format!(" on {} {}", iface, net.cidr());
+    }
+    s
+}
+
+impl fmt::Display for Collision {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} collides with {}",
+            self.address,
+            describe(&self.second),
+            describe(&self.first)
+        )
+    }
+}
+
+/// Where an address has to be unique. Ips are unique everywhere, macs only
+/// on the link of the entry, which is its interface or else its network.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Key(Option<String>, Address);
+
+fn link(e: &Entry) -> Option<String> {
+    e.network.as_ref().map(|net| match &net.iface {
+        Some(iface) => iface.name.clone(),
+        None => net.cidr().to_string(),
+    })
+}
+
+fn keys(e: &Entry) -> Vec<Key> {
+    let mut keys = vec![Key(None, Address::Ip(e.ip))];
+    if let Some(mac) = e.mac {
+        keys.push(Key(link(e), Address::Mac(mac)));
+    }
+    keys
+}
+
+fn release(holders: &mut HashMap<Key, Vec<usize>>, key: &Key, i: usize) {
+    if let Some(held) = holders.get_mut(key) {
+        held.retain(|&j| j != i);
+    }
+}
+
+/// Finds entries of distinct hosts sharing an ip address, or a mac address
+/// on the same link, within a source and across sources, and applies
+/// `policy` to them. Losing an ip drops the entry, losing only a mac keeps
+/// the entry without it. Entries are given per source, in the order of the
+/// sources, and returned the same way.
+pub fn resolve_collisions(
+    sources: Vec<Vec<Entry>>,
+    policy: CollisionPolicy,
+) -> Result<Vec<Vec<Entry>>, Vec<Collision>> {
+    let mut entries: Vec<Option<Entry>> = Vec::new();
+    let mut lens = Vec::new();
+    // the kept entries with each address
+    let mut holders: HashMap<Key, Vec<usize>> = HashMap::new();
+    let mut reported = HashSet::new();
+    let mut collisions = Vec::new();
+
+    for source in sources {
+        lens.push(source.len());
+        for mut e in source {
+            let mut keep = true;
+            for key in keys(&e) {
+                let others: Vec<usize> = holders
+                    .get(&key)
+                    .into_iter()
+                    .flatten()
+                    .copied()
+                    .filter(|&i| matches!(&entries[i], Some(h) if h.name != e.name))
+                    .collect();
+                let first = match others.first().and_then(|&i| entries[i].clone()) {
+                    Some(first) => first,
+                    None => continue,
+                };
+                // a shared mac may collide on several links, report it once
+                if reported.insert((key.1, first.name.clone(), e.name.clone())) {
+                    collisions.push(Collision {
+                        address: key.1,
+                        first,
+                        second: e.clone(),
+                    });
+                }
+                match (policy, key.1) {
+                    (CollisionPolicy::FirstWins, Address::Ip(_)) => keep = false,
+                    (CollisionPolicy::FirstWins, Address::Mac(_)) => e.mac = None,
+                    (CollisionPolicy::LastWins, Address::Ip(_)) => {
+                        for i in others {
+                            if let Some(prev) = entries[i].take() {
+                                for key in keys(&prev) {
+                                    release(&mut holders, &key, i);
+                                }
+                            }
+                        }
+                    }
+                    (CollisionPolicy::LastWins, Address::Mac(_)) => {
+                        for i in others {
+                            if let Some(prev) = entries[i].as_mut() {
+                                prev.mac = None;
+                            }
+                            release(&mut holders, &key, i);
+                        }
+                    }
+                    (CollisionPolicy::Error, _) | (CollisionPolicy::Warn, _) => {}
+                }
+            }
+            if keep {
+                for key in keys(&e) {
+                    holders.entry(key).or_default().push(entries.len());
+                }
+                entries.push(Some(e));
+            } else {
+                entries.push(None);
+            }
+        }
+    }
+
+    if policy == CollisionPolicy::Error && !collisions.is_empty() {
+        return Err(collisions);
+    }
+    for c in &collisions {
+        warn!("{}", c);
+    }
+
+    let mut entries = entries.into_iter();
+    Ok(lens
+        .into_iter()
+        .map(|len| entries.by_ref().take(len).flatten().collect())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::InterfaceNetwork;
+    use serde_yaml::Value;
+
+    fn network(iface: &str) -> InterfaceNetwork {
+        let val: Value = serde_yaml::from_str("eth0: [10.1.0.1/24]\neth1: [10.2.0.1/24]").unwrap();
+        InterfaceNetwork::from_val(&val)
+            .into_iter()
+            .find(|n| n.iface.as_ref().unwrap().name == iface)
+            .unwrap()
+    }
+
+    fn entry(name: &str, mac: Option<u8>, ip: &str, iface: &str) -> Entry {
+        let mac = mac.map(|m| MacAddr::new(2, 0, 0, 0, 0, m));
+        Entry::new_with_network(name, mac, ip.parse().unwrap(), network(iface))
+    }
+
+    fn kept(sources: &[Vec<Entry>]) -> Vec<Vec<(String, Option<MacAddr>)>> {
+        sources
+            .iter()
+            .map(|s| s.iter().map(|e| (e.name.clone(), e.mac)).collect())
+            .collect()
+    }
+
+    fn names(expected: &[&[(&str, Option<u8>)]]) -> Vec<Vec<(String, Option<MacAddr>)>> {
+        expected
+            .iter()
+            .map(|s| {
+                s.iter()
+                    .map(|(n, m)| (n.to_string(), m.map(|m| MacAddr::new(2, 0, 0, 0, 0, m))))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn shared_ip() -> Vec<Vec<Entry>> {
+        vec![
+            vec![entry("a", Some(1), "10.1.0.5", "eth0")],
+            vec![entry("b", Some(2), "10.1.0.5", "eth0")],
+        ]
+    }
+
+    #[test]
+    fn warn_keeps_everything() {
+        let sources = resolve_collisions(shared_ip(), CollisionPolicy::Warn)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            kept(&sources),
+            names(&[&[("a", Some(1))], &[("b", Some(2))]])
+        );
+    }
+
+    #[test]
+    fn error_reports_collisions() {
+        let collisions = resolve_collisions(shared_ip(), CollisionPolicy::Error)
+            .err()
+            .unwrap();
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions[0].address,
+            Address::Ip("10.1.0.5".parse().unwrap())
+        );
+        assert_eq!(collisions[0].first.name, "a");
+        assert_eq!(collisions[0].second.name, "b");
+    }
+
+    #[test]
+    fn first_wins_drops_the_later_entry() {
+        let sources = resolve_collisions(shared_ip(), CollisionPolicy::FirstWins)
+            .ok()
+            .unwrap();
+        assert_eq!(kept(&sources), names(&[&[("a", Some(1))], &[]]));
+    }
+
+    #[test]
+    fn last_wins_drops_the_earlier_entry() {
+        let sources = resolve_collisions(shared_ip(), CollisionPolicy::LastWins)
+            .ok()
+            .unwrap();
+        assert_eq!(kept(&sources), names(&[&[], &[("b", Some(2))]]));
+    }
+
+    #[test]
+    fn last_wins_forgets_the_addresses_of_dropped_entries() {
+        let mut sources = shared_ip();
+        sources.push(vec![entry("c", Some(1), "10.1.0.6", "eth0")]);
+        let collisions = resolve_collisions(sources.clone(), CollisionPolicy::Error)
+            .err()
+            .unwrap();
+        assert_eq!(collisions.len(), 2);
+        let sources = resolve_collisions(sources, CollisionPolicy::LastWins)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            kept(&sources),
+            names(&[&[], &[("b", Some(2))], &[("c", Some(1))]])
+        );
+    }
+
+    #[test]
+    fn mac_only_collision_drops_the_mac() {
+        let sources = vec![vec![
+            entry("a", Some(1), "10.1.0.5", "eth0"),
+            entry("b", Some(1), "10.1.0.6", "eth0"),
+        ]];
+        let first = resolve_collisions(sources.clone(), CollisionPolicy::FirstWins)
+            .ok()
+            .unwrap();
+        assert_eq!(kept(&first), names(&[&[("a", Some(1)), ("b", None)]]));
+        let last = resolve_collisions(sources, CollisionPolicy::LastWins)
+            .ok()
+            .unwrap();
+        assert_eq!(kept(&last), names(&[&[("a", None), ("b", Some(1))]]));
+    }
+
+    #[test]
+    fn macs_collide_only_on_the_same_link() {
+        let sources = vec![vec![
+            entry("a", Some(10), "10.1.0.10", "eth0"),
+            entry("b", Some(10), "10.2.0.10", "eth1"),
+        ]];
+        assert!(resolve_collisions(sources, CollisionPolicy::Error).is_ok());
+    }
+
+    #[test]
+    fn a_host_does_not_collide_with_itself() {
+        let sources = vec![
+            vec![entry("a", Some(1), "10.1.0.5", "eth0")],
+            vec![entry("a", Some(1), "10.1.0.5", "eth0")],
+        ];
+        let sources = resolve_collisions(sources, CollisionPolicy::Error)
+            .ok()
+            .unwrap();
+        assert_eq!(
+            kept(&sources),
+            names(&[&[("a", Some(1))], &[("a", Some(1))]])
+        );
+    }
+}
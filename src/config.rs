@@ -0,0 +1,29 @@
+use crate::error::Result;
+use serde_yaml::Value;
+use std::fs::File;
+
+// Top level keys holding settings rather than interface selectors.
+const SETTINGS_KEYS: &[&str] = &["zone", "outputs", "networks", "address_policy"];
+
+pub fn is_settings_key(key: &Value) -> bool {
+    key.as_str()
+        .filter(|k| SETTINGS_KEYS.contains(&k.to_lowercase().as_ref()))
+        .is_some()
+}
+
+pub fn settings_section<'a>(val: &'a Value, key: &str) -> Option<&'a Value> {
+    match val {
+        Value::Sequence(seq) => seq.iter().filter_map(|v| settings_section(v, key)).next(),
+        Value::Mapping(map) => map
+            .iter()
+            .filter(|(k, _)| k.as_str().filter(|k| k.eq_ignore_ascii_case(key)).is_some())
+            .map(|(_, v)| v)
+            .next(),
+        _ => None,
+    }
+}
+
+/// Reads a yaml config file.
+pub fn load(path: &str) -> Result<Value> {
+    Ok(serde_yaml::from_reader(File::open(path)?)?)
+}
@@ -1,17 +1,69 @@
-use crate::hosts::Host;
-use crate::network::InterfaceNetwork;
+use crate::check::{check_val, Diagnostic};
+use crate::config::is_settings_key;
+use crate::error::Error;
+use crate::hosts::{Host, Ipv4Addrs};
+use crate::ipnet::ToReverseName;
+use crate::network::{InterfaceNetwork, LiveNetworks, NetworkProvider, StaticNetworks};
 use crate::chain::ChainedEntryIterator;
+use crate::template::Template;
+use crate::zone::ZoneHeader;
 use log::warn;
+use ipnetwork::IpNetwork;
 use pnet::datalink::MacAddr;
+use serde::{Serialize, Serializer};
+use serde_json::json;
 use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, HashMap};
 use std::io::{self, Write};
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv6Addr};
+use std::rc::Rc;
 use tabwriter::TabWriter;
 
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", content = "file", rename_all = "lowercase")]
+pub enum Source {
+    Config(String),
+    Leases(String),
+}
+
+#[derive(Clone)]
 pub struct Entry {
     pub name: String,
     pub mac: Option<MacAddr>,
     pub ip: IpAddr,
+    pub network: Option<InterfaceNetwork>,
+    pub source: Option<Source>,
+}
+
+// Flat representation of an entry for structured output.
+#[derive(Serialize)]
+struct EntryRecord<'a> {
+    name: &'a str,
+    mac: Option<String>,
+    ip: IpAddr,
+    family: &'static str,
+    source: Option<&'a Source>,
+    iface: Option<&'a str>,
+    network: Option<IpNetwork>,
+}
+
+impl Serialize for Entry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        EntryRecord {
+            name: &self.name,
+            mac: self.mac.map(|mac| mac.to_string()),
+            ip: self.ip,
+            family: if self.ip.is_ipv4() { "ipv4" } else { "ipv6" },
+            source: self.source.as_ref(),
+            iface: self
+                .network
+                .as_ref()
+                .and_then(|n| n.iface.as_ref())
+                .map(|i| i.name.as_ref()),
+            network: self.network.as_ref().map(InterfaceNetwork::cidr),
+        }
+        .serialize(serializer)
+    }
 }
 
 impl Entry {
@@ -20,6 +72,20 @@ impl Entry {
             name: name.to_string(),
             mac,
             ip,
+            network: None,
+            source: None,
+        }
+    }
+
+    pub fn new_with_network(
+        name: &str,
+        mac: Option<MacAddr>,
+        ip: IpAddr,
+        network: InterfaceNetwork,
+    ) -> Self {
+        Entry {
+            network: Some(network),
+            ..Self::new(name, mac, ip)
         }
     }
 
@@ -57,6 +123,97 @@ impl Entry {
         elems.join("\t")
     }
 
+    pub fn fqdn(&self, domain: Option<&str>) -> String {
+        if self.name.ends_with('.') {
+            return self.name.trim_end_matches('.').to_string();
+        }
+        match domain.map(|d| d.trim_matches('.')) {
+            Some(d) if !d.is_empty() && !self.name.ends_with(&format!(".{}", d)) => {
+                format!("{}.{}", self.name, d)
+            }
+            _ => self.name.to_string(),
+        }
+    }
+
+    // Entries without a real network (leases, null selectors) fall back to
+    // the conventional /24 and /64 reverse zones.
+    pub fn reverse_zone(&self) -> String {
+        let default = if self.ip.is_ipv4() { 24 } else { 64 };
+        let prefix = self
+            .network
+            .as_ref()
+            .map(|n| n.network.prefix())
+            .filter(|p| *p > 0)
+            .unwrap_or(default);
+        self.ip.to_reverse_zone(prefix)
+    }
+
+    pub fn as_ptr_entry(&self, domain: Option<&str>) -> String {
+        let name = self.ip.to_reverse_name();
+        let zone = self.reverse_zone();
+        let owner = name
+            .strip_suffix(&zone)
+            .map(|n| n.trim_end_matches('.'))
+            .filter(|n| !n.is_empty())
+            .unwrap_or("@");
+        format!("{}\tPTR\t{}.", owner, self.fqdn(domain))
+    }
+
+    pub fn as_hosts_entry(&self, domain: Option<&str>) -> String {
+        let fqdn = self.fqdn(domain);
+        let mut names = vec![fqdn.clone()];
+        if let Some(short) = domain
+            .map(|d| d.trim_matches('.'))
+            .and_then(|d| fqdn.strip_suffix(&format!(".{}", d)))
+        {
+            names.push(short.to_string());
+        }
+        format!("{}\t{}", self.ip, names.join(" "))
+    }
+
+    pub fn as_unbound_entry(&self, domain: Option<&str>) -> String {
+        let fqdn = self.fqdn(domain);
+        let rtype = if self.ip.is_ipv6() { "AAAA" } else { "A" };
+        format!(
+            "local-data: \"{}. IN {} {}\"\nlocal-data-ptr: \"{} {}.\"",
+            fqdn, rtype, self.ip, self.ip, fqdn
+        )
+    }
+
+    pub fn as_dhcpd_entry(&self, decl: &str) -> Option<String> {
+        let mac = match self.mac {
+            Some(mac) => mac,
+            None => {
+                warn!("skipping dhcpd host without mac: {} {}", self.name, self.ip);
+                return None;
+            }
+        };
+        let fixed = if self.ip.is_ipv6() {
+            "fixed-address6"
+        } else {
+            "fixed-address"
+        };
+        Some(format!(
+            "host {} {{\n  hardware ethernet {};\n  {} {};\n}}",
+            decl, mac, fixed, self.ip
+        ))
+    }
+
+    /// The low 64 bits of the ipv6 address, which `ip token` sets on the
+    /// host so it takes the same address from router advertisements. Tokens
+    /// do not apply to link-local addresses, nor to networks longer than /64,
+    /// where SLAAC does not work.
+    pub fn ip_token(&self) -> Option<Ipv6Addr> {
+        let (ip, net) = match (self.ip, &self.network) {
+            (IpAddr::V6(ip), Some(net)) if ip.segments()[0] & 0xffc0 != 0xfe80 => (ip, net),
+            _ => return None,
+        };
+        if net.network.prefix() > 64 {
+            return None;
+        }
+        Some(Ipv6Addr::from(u128::from(ip) & u128::from(u64::MAX)))
+    }
+
     pub fn as_env_var(&self) -> String {
         let v = if self.ip.is_ipv4() { "V4" } else { "V6" };
         format!(
@@ -77,12 +234,53 @@ where
     }
 
     fn as_zone_records(self) -> FormattedEntries<Self> {
-        FormattedEntries::ZoneRecords(self)
+        FormattedEntries::ZoneRecords(self, ZoneHeader::default())
+    }
+
+    fn as_zone_file(self, header: ZoneHeader) -> FormattedEntries<Self> {
+        FormattedEntries::ZoneRecords(self, header)
+    }
+
+    fn as_ptr_records(self, domain: Option<String>) -> FormattedEntries<Self> {
+        FormattedEntries::PtrRecords(self, domain)
+    }
+
+    fn as_hosts_file(self, opts: HostsOptions) -> FormattedEntries<Self> {
+        FormattedEntries::HostsFile(self, opts)
+    }
+
+    fn as_unbound_local_data(self, opts: UnboundOptions) -> FormattedEntries<Self> {
+        FormattedEntries::UnboundLocalData(self, opts)
+    }
+
+    fn as_dhcpd_hosts(self) -> FormattedEntries<Self> {
+        FormattedEntries::DhcpdHosts(self)
     }
+
+    fn as_kea_reservations(self) -> FormattedEntries<Self> {
+        FormattedEntries::KeaReservations(self)
+    }
+
+    fn as_json(self) -> FormattedEntries<Self> {
+        FormattedEntries::Json(self)
+    }
+
+    fn as_yaml(self) -> FormattedEntries<Self> {
+        FormattedEntries::Yaml(self)
+    }
+
+    fn as_templated(self, template: Template, domain: Option<String>) -> FormattedEntries<Self> {
+        FormattedEntries::Templated(self, template, domain)
+    }
+
     fn as_env_vars(self) -> FormattedEntries<Self> {
         FormattedEntries::EnvVars(self)
     }
 
+    fn as_ip_tokens(self, dev: Option<String>) -> FormattedEntries<Self> {
+        FormattedEntries::IpTokens(self, dev)
+    }
+
     fn chain_entries<J: Iterator<Item=Entry> + Sized>(self, other: J) -> ChainedEntryIterator<Self, J> {
         ChainedEntryIterator::new(self, other)
     }
@@ -92,19 +290,55 @@ impl<I: Iterator<Item = Entry> + Sized> EntryIterator for I {}
 
 pub enum FormattedEntries<I: Iterator<Item = Entry> + Sized> {
     DnsmasqReservations(I),
-    ZoneRecords(I),
+    ZoneRecords(I, ZoneHeader),
+    PtrRecords(I, Option<String>),
+    HostsFile(I, HostsOptions),
+    UnboundLocalData(I, UnboundOptions),
+    DhcpdHosts(I),
+    KeaReservations(I),
+    Json(I),
+    Yaml(I),
+    Templated(I, Template, Option<String>),
     EnvVars(I),
+    // the device of the hosts the tokens are set on, eth0 when not given
+    IpTokens(I, Option<String>),
+}
+
+#[derive(Clone, Default)]
+pub struct HostsOptions {
+    pub domain: Option<String>,
+    // keep all addresses of a host together
+    pub group: bool,
+    // start with the usual localhost entries
+    pub localhost: bool,
+}
+
+#[derive(Clone, Default)]
+pub struct UnboundOptions {
+    pub domain: Option<String>,
+    // type of the local-zone declared for the domain, none when not declared
+    pub local_zone: Option<String>,
 }
 
 impl<I: Iterator<Item = Entry> + Sized> FormattedEntries<I> {
     pub fn write<W: io::Write>(self, w: &mut W) -> std::io::Result<()> {
         match self {
-            Self::ZoneRecords(_) => {
+            Self::ZoneRecords(..)
+            | Self::PtrRecords(..)
+            | Self::HostsFile(..)
+            | Self::IpTokens(..) => {
                 let mut w = TabWriter::new(w);
                 self.raw_write(&mut w)?;
                 w.flush()
             }
-            Self::DnsmasqReservations(_) | Self::EnvVars(_) => self.raw_write(w),
+            Self::DnsmasqReservations(_)
+            | Self::UnboundLocalData(..)
+            | Self::DhcpdHosts(_)
+            | Self::KeaReservations(_)
+            | Self::Json(_)
+            | Self::Yaml(_)
+            | Self::Templated(..)
+            | Self::EnvVars(_) => self.raw_write(w),
         }
     }
     fn raw_write<W: io::Write>(self, w: &mut W) -> std::io::Result<()> {
@@ -118,51 +352,319 @@ impl<I: Iterator<Item = Entry> + Sized> FormattedEntries<I> {
 impl<I: Iterator<Item = Entry> + Sized> IntoIterator for FormattedEntries<I> {
     type Item = String;
 
-    type IntoIter = std::iter::Map<I, fn(I::Item) -> String>;
+    type IntoIter = std::vec::IntoIter<String>;
     fn into_iter(self) -> Self::IntoIter {
-        match self {
-            Self::DnsmasqReservations(i) => i.map(|e| e.as_dnsmasq_entry()),
-            Self::ZoneRecords(i) => i.map(|e| e.as_zone_entry()),
-            Self::EnvVars(i) => i.map(|e| e.as_env_var()),
+        let lines: Vec<String> = match self {
+            Self::DnsmasqReservations(i) => i.map(|e| e.as_dnsmasq_entry()).collect(),
+            Self::ZoneRecords(i, header) => header
+                .lines()
+                .into_iter()
+                .chain(i.map(|e| e.as_zone_entry()))
+                .collect(),
+            Self::PtrRecords(i, domain) => {
+                // group records by reverse zone, each introduced by its $ORIGIN
+                let mut zones: BTreeMap<String, Vec<String>> = BTreeMap::new();
+                for e in i {
+                    zones
+                        .entry(e.reverse_zone())
+                        .or_default()
+                        .push(e.as_ptr_entry(domain.as_deref()));
+                }
+                zones
+                    .into_iter()
+                    .flat_map(|(zone, records)| {
+                        std::iter::once(format!("$ORIGIN {}.", zone)).chain(records)
+                    })
+                    .collect()
+            }
+            Self::HostsFile(i, opts) => {
+                let mut lines = Vec::new();
+                if opts.localhost {
+                    lines.push("127.0.0.1\tlocalhost".to_string());
+                    lines.push("::1\tlocalhost ip6-localhost ip6-loopback".to_string());
+                }
+                let domain = opts.domain.as_deref();
+                if opts.group {
+                    let mut hosts: Vec<(String, Vec<Entry>)> = Vec::new();
+                    for e in i {
+                        match hosts.iter_mut().find(|(name, _)| *name == e.name) {
+                            Some((_, entries)) => entries.push(e),
+                            None => hosts.push((e.name.clone(), vec![e])),
+                        }
+                    }
+                    lines.extend(
+                        hosts
+                            .into_iter()
+                            .flat_map(|(_, entries)| entries)
+                            .map(|e| e.as_hosts_entry(domain)),
+                    );
+                } else {
+                    lines.extend(i.map(|e| e.as_hosts_entry(domain)));
+                }
+                lines
+            }
+            Self::UnboundLocalData(i, opts) => {
+                let domain = opts.domain.as_deref();
+                let mut lines = Vec::new();
+                match (domain, &opts.local_zone) {
+                    (Some(d), Some(zone_type)) => lines.push(format!(
+                        "local-zone: \"{}.\" {}",
+                        d.trim_matches('.'),
+                        zone_type
+                    )),
+                    (None, Some(_)) => warn!("unbound local-zone requires a domain"),
+                    _ => {}
+                }
+                lines.extend(i.map(|e| e.as_unbound_entry(domain)));
+                lines
+            }
+            Self::DhcpdHosts(i) => {
+                // host declarations need unique names, number repeated hosts
+                let mut seen: HashMap<String, usize> = HashMap::new();
+                i.filter_map(|e| {
+                    let mut decl = e.name.clone();
+                    if e.mac.is_some() {
+                        let n = seen.entry(e.name.clone()).or_default();
+                        *n += 1;
+                        if *n > 1 {
+                            decl = format!("{}-{}", e.name, n);
+                        }
+                    }
+                    e.as_dhcpd_entry(&decl)
+                })
+                .collect()
+            }
+            Self::KeaReservations(i) => vec![kea_config(i)],
+            Self::Json(i) => {
+                let entries: Vec<_> = i.collect();
+                vec![serde_json::to_string_pretty(&entries).unwrap_or_default()]
+            }
+            Self::Yaml(i) => {
+                let entries: Vec<_> = i.collect();
+                vec![serde_yaml::to_string(&entries).unwrap_or_default()]
+            }
+            Self::Templated(i, template, domain) => template
+                .header
+                .clone()
+                .into_iter()
+                .chain(i.map(|e| template.render(&e, domain.as_deref())))
+                .chain(template.footer.clone())
+                .collect(),
+            Self::EnvVars(i) => i.map(|e| e.as_env_var()).collect(),
+            Self::IpTokens(i, dev) => {
+                // a device has a single token, keep the first of each host
+                let dev = dev.unwrap_or_else(|| "eth0".to_string());
+                let mut tokens: Vec<(String, Ipv6Addr)> = Vec::new();
+                for e in i {
+                    let token = match e.ip_token() {
+                        Some(t) => t,
+                        None => continue,
+                    };
+                    match tokens.iter().find(|(name, _)| *name == e.name) {
+                        Some((_, first)) if *first != token => warn!(
+                            "skipping ip token {} of {}, it already has {}",
+                            token, e.name, first
+                        ),
+                        Some(_) => {}
+                        None => tokens.push((e.name.clone(), token)),
+                    }
+                }
+                tokens
+                    .into_iter()
+                    .map(|(name, token)| {
+                        format!("ip token set {} dev {}\t# {}", token, dev, name)
+                    })
+                    .collect()
+            }
+        };
+        lines.into_iter()
+    }
+}
+
+// Groups reservations by the subnet they were generated for, ipv6 hosts get
+// all their addresses in a subnet in a single reservation.
+fn kea_config<I: Iterator<Item = Entry>>(entries: I) -> String {
+    type Reservation = (String, MacAddr, Vec<IpAddr>);
+    let mut subnets: Vec<(IpNetwork, Vec<Reservation>)> = Vec::new();
+    for e in entries {
+        let net = match &e.network {
+            Some(n) => n.cidr(),
+            None => {
+                warn!(
+                    "skipping kea reservation without network: {} {}",
+                    e.name, e.ip
+                );
+                continue;
+            }
+        };
+        let mac = match e.mac {
+            Some(mac) => mac,
+            None => {
+                warn!("skipping kea reservation without mac: {} {}", e.name, e.ip);
+                continue;
+            }
+        };
+        let hosts = match subnets.iter_mut().find(|(n, _)| *n == net) {
+            Some((_, hosts)) => hosts,
+            None => {
+                subnets.push((net, Vec::new()));
+                &mut subnets.last_mut().unwrap().1
+            }
+        };
+        // kea refuses a subnet with two reservations for the same mac
+        match hosts.iter_mut().find(|(_, m, _)| *m == mac) {
+            Some((name, _, ips)) if net.is_ipv6() && *name == e.name => ips.push(e.ip),
+            Some((name, _, _)) => warn!(
+                "skipping kea reservation of {} {}, {} already has mac {} in {}",
+                e.name, e.ip, name, mac, net
+            ),
+            None => hosts.push((e.name, mac, vec![e.ip])),
         }
     }
+
+    let (v4, v6): (Vec<_>, Vec<_>) = subnets.into_iter().partition(|(n, _)| n.is_ipv4());
+    let subnet4: Vec<_> = v4
+        .into_iter()
+        .map(|(net, hosts)| {
+            let reservations: Vec<_> = hosts
+                .into_iter()
+                .map(|(name, mac, ips)| {
+                    json!({
+                        "hw-address": mac.to_string(),
+                        "ip-address": ips[0].to_string(),
+                        "hostname": name,
+                    })
+                })
+                .collect();
+            json!({ "subnet": net.to_string(), "reservations": reservations })
+        })
+        .collect();
+    let subnet6: Vec<_> = v6
+        .into_iter()
+        .map(|(net, hosts)| {
+            let reservations: Vec<_> = hosts
+                .into_iter()
+                .map(|(name, mac, ips)| {
+                    let ips: Vec<_> = ips.iter().map(IpAddr::to_string).collect();
+                    json!({
+                        "hw-address": mac.to_string(),
+                        "ip-addresses": ips,
+                        "hostname": name,
+                    })
+                })
+                .collect();
+            json!({ "subnet": net.to_string(), "reservations": reservations })
+        })
+        .collect();
+
+    let config = json!({
+        "Dhcp4": { "subnet4": subnet4 },
+        "Dhcp6": { "subnet6": subnet6 },
+    });
+    serde_json::to_string_pretty(&config).unwrap_or_default()
 }
 
 pub fn entries_from_val(val: Value) -> impl Iterator<Item = Entry> {
+    entries_from_val_with(val, &LiveNetworks)
+}
+
+/// Like `entries_from_val`, selecting from the networks of `provider`
+/// instead of the live interfaces.
+pub fn entries_from_val_with(
+    val: Value,
+    provider: &dyn NetworkProvider,
+) -> impl Iterator<Item = Entry> {
+    let networks = Rc::new(provider.networks());
+    let ipv4s = Rc::new(Ipv4Addrs::from_val(&val, &networks));
     match val {
-        Value::Sequence(seq) => entries_from_seq(seq),
-        _ => entries_from_seq(vec![val]),
+        Value::Sequence(seq) => entries_from_seq(seq, networks, ipv4s),
+        _ => entries_from_seq(vec![val], networks, ipv4s),
+    }
+}
+
+/// Like `entries_from_val_with`, but fails with everything `check_val`
+/// finds in `val` instead of warning about it and skipping it.
+pub fn try_entries_from_val_with(
+    val: Value,
+    provider: &dyn NetworkProvider,
+) -> Result<Vec<Entry>, Vec<Diagnostic>> {
+    let networks = provider.networks();
+    let mut found = Vec::new();
+    check_val(&val, &networks, &mut found);
+    if !found.is_empty() {
+        return Err(found);
     }
+    Ok(entries_from_val_with(val, &StaticNetworks(networks)).collect())
 }
 
-fn entries_from_seq(seq: serde_yaml::Sequence) -> impl Iterator<Item = Entry> {
+fn entries_from_seq(
+    seq: serde_yaml::Sequence,
+    networks: Rc<Vec<InterfaceNetwork>>,
+    ipv4s: Rc<Ipv4Addrs>,
+) -> impl Iterator<Item = Entry> {
     seq.into_iter()
-        .filter_map(|v| match v {
-            Value::Mapping(map) => Some(entries_from_map(map)),
+        .filter_map(move |v| match v {
+            Value::Mapping(map) => Some(entries_from_map(
+                map,
+                networks.clone(),
+                ipv4s.clone(),
+            )),
             _ => {
-                warn!("invalid entry map: {:?}", v);
+                warn!("{}", Error::InvalidEntryMap(v));
                 None
             }
         })
         .flatten()
 }
 
-fn entries_from_map(map: Mapping) -> impl Iterator<Item = Entry> {
-    map.into_iter().flat_map(|(k, v)| {
-        let nets = InterfaceNetwork::filtered(&k);
-        Host::new_hosts(v).flat_map(move |h| {
-            nets.clone().into_iter().filter_map(move |net| {
-                let ip = h.get_ip(&net)?;
-                Some(Entry::new(&h.name, h.get_mac(&net), ip))
+fn entries_from_map(
+    map: Mapping,
+    networks: Rc<Vec<InterfaceNetwork>>,
+    ipv4s: Rc<Ipv4Addrs>,
+) -> impl Iterator<Item = Entry> {
+    let selectable = networks.clone();
+    map.into_iter()
+        .filter(move |(k, _)| {
+            if !is_settings_key(k) {
+                return true;
+            }
+            if !InterfaceNetwork::filter_networks(&selectable, k).is_empty() {
+                warn!("{}", Error::ShadowedSelector(k.clone()));
+            }
+            false
+        })
+        .flat_map(move |(k, v)| {
+            let nets = InterfaceNetwork::filter_networks(&networks, &k);
+            let ipv4s = ipv4s.clone();
+            Host::new_hosts(v).flat_map(move |h| {
+                let ipv4s = ipv4s.clone();
+                nets.clone().into_iter().filter_map(move |net| {
+                    let ip = h.get_ip(&net, &ipv4s)?;
+                    let mac = h.get_mac(&net);
+                    // the none networks only stand in for the lack of one
+                    if net.is_none_network() {
+                        return Some(Entry::new(&h.name, mac, ip));
+                    }
+                    Some(Entry::new_with_network(&h.name, mac, ip, net))
+                })
             })
         })
-    })
 }
 
 pub fn entries_from_dnsmasq_leases<I: Iterator<Item=String>>(lines: I) -> impl Iterator<Item = Entry> {
     lines.filter_map(|l| Entry::from_dnsmasq_lease(&l))
 }
 
+pub fn entries_with_source<I: Iterator<Item = Entry>>(
+    entries: I,
+    source: Source,
+) -> impl Iterator<Item = Entry> {
+    entries.map(move |mut e| {
+        e.source = Some(source.clone());
+        e
+    })
+}
+
 pub enum EntryIteratorFrom<V: Iterator<Item=Entry>, D: Iterator<Item=Entry>> {
     Val(V),
     DnsMasq(D),
@@ -176,4 +678,126 @@ impl<V: Iterator<Item=Entry>, D: Iterator<Item=Entry>> Iterator for EntryIterato
             Self::DnsMasq(d) => d.next(),
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn networks() -> StaticNetworks {
+        let val: Value =
+            serde_yaml::from_str("eth0: [10.1.0.1/24, \"2001:db8::1/64\"]\nwg0: [10.9.0.1/24]")
+                .unwrap();
+        let mut networks = InterfaceNetwork::from_val(&val);
+        networks.extend(InterfaceNetwork::none_networks());
+        StaticNetworks(networks)
+    }
+
+    fn entries(config: &str) -> Vec<Entry> {
+        entries_from_val_with(serde_yaml::from_str(config).unwrap(), &networks()).collect()
+    }
+
+    fn addresses(entries: &[Entry]) -> Vec<(String, String)> {
+        entries
+            .iter()
+            .map(|e| (e.name.clone(), e.ip.to_string()))
+            .collect()
+    }
+
+    fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(name, ip)| (name.to_string(), ip.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn integer_in_every_network_of_the_interface() {
+        let entries = entries("eth0:\n  server1: 10");
+        assert_eq!(
+            addresses(&entries),
+            pairs(&[("server1", "10.1.0.10"), ("server1", "2001:db8::ff:fe00:a")])
+        );
+        assert!(entries
+            .iter()
+            .all(|e| e.mac == Some(MacAddr::new(2, 0, 0, 0, 0, 10))));
+        assert!(entries.iter().all(|e| e
+            .network
+            .as_ref()
+            .and_then(|n| n.iface.as_ref())
+            .unwrap()
+            .name
+            == "eth0"));
+    }
+
+    #[test]
+    fn network_selector() {
+        let entries = entries("\"10.0.0.0/8\":\n  server1: 10");
+        assert_eq!(
+            addresses(&entries),
+            pairs(&[("server1", "10.1.0.10"), ("server1", "10.9.0.10")])
+        );
+    }
+
+    #[test]
+    fn explicit_ipv4_keeps_the_host_bits() {
+        let entries = entries("wg0:\n  server1: 192.168.1.5");
+        assert_eq!(addresses(&entries), pairs(&[("server1", "10.9.0.5")]));
+    }
+
+    #[test]
+    fn null_selector_has_no_network() {
+        let entries = entries("~:\n  server1: 192.168.1.5");
+        assert_eq!(entries[0].ip.to_string(), "192.168.1.5");
+        assert!(entries.iter().all(|e| e.network.is_none()));
+    }
+
+    #[test]
+    fn selector_offset() {
+        let entries = entries("? \"10.1.0.0/24\": {offset: 100}\n: server1: 10");
+        assert_eq!(addresses(&entries), pairs(&[("server1", "10.1.0.110")]));
+    }
+
+    #[test]
+    fn host_range() {
+        let entries = entries("wg0:\n  node[08-10]: 20+");
+        assert_eq!(
+            addresses(&entries),
+            pairs(&[
+                ("node08", "10.9.0.28"),
+                ("node09", "10.9.0.29"),
+                ("node10", "10.9.0.30")
+            ])
+        );
+    }
+
+    #[test]
+    fn settings_keys_are_not_selectors() {
+        let entries = entries("zone:\n  origin: example.com\nwg0:\n  server1: 10");
+        assert_eq!(addresses(&entries), pairs(&[("server1", "10.9.0.10")]));
+    }
+
+    #[test]
+    fn try_entries_fails_on_unmatched_selector() {
+        let val = serde_yaml::from_str("eth9:\n  server1: 10").unwrap();
+        let found = try_entries_from_val_with(val, &networks()).err().unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(matches!(found[0].error, Error::UnmatchedSelector(_)));
+    }
+
+    #[test]
+    fn try_entries_succeeds_on_valid_config() {
+        let val = serde_yaml::from_str("wg0:\n  server1: 10").unwrap();
+        let entries = try_entries_from_val_with(val, &networks()).ok().unwrap();
+        assert_eq!(addresses(&entries), pairs(&[("server1", "10.9.0.10")]));
+    }
+
+    #[test]
+    fn ptr_entry_is_relative_to_the_reverse_zone() {
+        let entries = entries("wg0:\n  server1: 10");
+        assert_eq!(entries[0].reverse_zone(), "0.9.10.in-addr.arpa");
+        assert_eq!(
+            entries[0].as_ptr_entry(Some("example.com")),
+            "10\tPTR\tserver1.example.com."
+        );
+    }
+}
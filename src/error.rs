@@ -0,0 +1,74 @@
+use serde_yaml::Value;
+use std::fmt;
+use std::io;
+
+/// What can be wrong with a config. Generation warns about these and skips
+/// what they apply to, the `try_*` functions and `check` return them.
+#[derive(Debug)]
+pub enum Error {
+    InvalidEntryMap(Value),
+    InvalidHostMap(Value),
+    InvalidHostName(Value),
+    UnknownLabel(Value),
+    InvalidOption(Value),
+    InvalidSelector(Value),
+    UnmatchedSelector(Value),
+    ShadowedSelector(Value),
+    // a host, the ipv6 address it got from an integer and its mac address
+    // a host asking for stable privacy addresses
+    MissingSecret(String),
+    Io(io::Error),
+    Yaml(serde_yaml::Error),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidEntryMap(v) => write!(f, "invalid entry map: {:?}", v),
+            Self::InvalidHostMap(v) => write!(f, "invalid host map: {:?}", v),
+            Self::InvalidHostName(v) => write!(f, "invalid host name: {:?}", v),
+            Self::UnknownLabel(Value::String(s)) => write!(f, "unknown label key: {}", s),
+            Self::UnknownLabel(v) => write!(f, "unknown label key: {:?}", v),
+            Self::InvalidOption(v) => write!(f, "unable to convert val: {:?}", v),
+            Self::InvalidSelector(v) => write!(f, "invalid selector: {:?}", v),
+            Self::UnmatchedSelector(_) => write!(f, "selector matches no networks"),
+            Self::ShadowedSelector(v) => write!(
+                f,
+                "{} is a settings key, not a selector, write [{}] to select its networks",
+                v.as_str().unwrap_or_default(),
+                v.as_str().unwrap_or_default()
+            ),
+            Self::MissingSecret(host) => write!(
+                f,
+                "{} asks for stable privacy addresses, which need a --secret-file",
+                host
+            ),
+            Self::Io(e) => write!(f, "unable to read: {}", e),
+            Self::Yaml(e) => write!(f, "unable to parse yaml: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Yaml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_yaml::Error> for Error {
+    fn from(e: serde_yaml::Error) -> Self {
+        Self::Yaml(e)
+    }
+}
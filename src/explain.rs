@@ -0,0 +1,113 @@
+use crate::config::is_settings_key;
+use crate::hosts::{Choice, Considered, Host, Ipv4Addrs};
+use crate::network::{InterfaceNetwork, NetworkProvider};
+use serde::Serialize;
+use serde_yaml::Value;
+use std::fmt;
+
+/// How a host got its mac and ip address on one of the networks its
+/// selector matched.
+#[derive(Serialize)]
+pub struct Explanation {
+    pub host: String,
+    pub selector: Value,
+    pub iface: Option<String>,
+    pub network: String,
+    pub mac: Choice,
+    pub ip: Choice,
+}
+
+impl Explanation {
+    fn new(host: &Host, selector: &Value, net: &InterfaceNetwork, ipv4s: &Ipv4Addrs) -> Self {
+        Self {
+            host: host.name.clone(),
+            selector: selector.clone(),
+            iface: net.iface.as_ref().map(|i| i.name.clone()),
+            network: net.cidr().to_string(),
+            mac: host.explain_mac(net),
+            ip: host.explain_ip(net, ipv4s),
+        }
+    }
+}
+
+/// Explains every host on every network it is selected for, walking the
+/// config the same way as `entries_from_val_with`.
+pub fn explain_val_with(val: &Value, provider: &dyn NetworkProvider) -> Vec<Explanation> {
+    let networks = provider.networks();
+    let ipv4s = Ipv4Addrs::from_val(val, &networks);
+    let maps = match val {
+        Value::Sequence(seq) => seq.iter().collect(),
+        _ => vec![val],
+    };
+    maps.into_iter()
+        .filter_map(Value::as_mapping)
+        .flat_map(|map| map.iter().filter(|(k, _)| !is_settings_key(k)))
+        .flat_map(|(k, v)| {
+            let nets = InterfaceNetwork::filter_networks(&networks, k);
+            Host::new_hosts(v.clone())
+                .flat_map(|h| {
+                    nets.iter()
+                        .map(|net| Explanation::new(&h, k, net, &ipv4s))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn selector_to_string(selector: &Value) -> String {
+    match selector {
+        Value::String(s) => s.clone(),
+        _ => serde_json::to_string(selector).unwrap_or_default(),
+    }
+}
+
+fn write_choice(f: &mut fmt::Formatter, kind: &str, choice: &Choice) -> fmt::Result {
+    match &choice.chosen {
+        Some(c) => {
+            writeln!(
+                f,
+                "  {} {} from {} ({})",
+                kind,
+                c.value.as_deref().unwrap_or_default(),
+                c.opt,
+                c.tier
+            )?;
+            if let Some(note) = &c.note {
+                writeln!(f, "    warning: {}", note)?;
+            }
+        }
+        None => writeln!(f, "  no {}", kind)?,
+    }
+    for Considered {
+        tier,
+        opt,
+        value,
+        note,
+    } in &choice.skipped
+    {
+        match (value, note) {
+            (Some(value), _) => {
+                writeln!(f, "    skipped {} ({}), would give {}", opt, tier, value)?
+            }
+            (None, Some(note)) => writeln!(f, "    skipped {} ({}), {}", opt, tier, note)?,
+            (None, None) => writeln!(f, "    skipped {} ({}), not usable", opt, tier)?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} on {} {}, selected by {}",
+            self.host,
+            self.iface.as_deref().unwrap_or("-"),
+            self.network,
+            selector_to_string(&self.selector)
+        )?;
+        write_choice(f, "mac", &self.mac)?;
+        write_choice(f, "ip", &self.ip)
+    }
+}
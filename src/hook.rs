@@ -0,0 +1,128 @@
+use log::warn;
+use serde_yaml::Value;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::process::Command;
+
+const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("WINCH", libc::SIGWINCH),
+];
+
+// The number of a signal given by name, like `HUP`, or by number.
+fn signal_number(signal: &str) -> Option<libc::c_int> {
+    SIGNALS
+        .iter()
+        .find(|(name, _)| *name == signal)
+        .map(|(_, n)| *n)
+        .or_else(|| signal.parse().ok())
+}
+
+#[derive(Clone, Debug)]
+pub enum Hook {
+    // run through `sh -c`
+    Command(String),
+    // send a signal to the pid in a pidfile
+    Signal { signal: String, pidfile: String },
+}
+
+impl fmt::Display for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Command(cmd) => write!(f, "`{}`", cmd),
+            Self::Signal { signal, pidfile } => write!(f, "SIG{} to {}", signal, pidfile),
+        }
+    }
+}
+
+impl Hook {
+    pub fn hooks_from_val(val: &Value) -> Vec<Self> {
+        match val {
+            Value::Sequence(seq) => seq.iter().filter_map(Self::from_val).collect(),
+            _ => Self::from_val(val).into_iter().collect(),
+        }
+    }
+
+    fn from_val(val: &Value) -> Option<Self> {
+        if let Some(cmd) = val
+            .as_str()
+            .or_else(|| val.get("command").and_then(Value::as_str))
+        {
+            return Some(Self::Command(cmd.to_string()));
+        }
+        let signal = val.get("signal").and_then(Value::as_str);
+        let pidfile = val.get("pidfile").and_then(Value::as_str);
+        let signal = signal.map(|s| s.trim_start_matches("SIG").to_uppercase());
+        match (signal, pidfile) {
+            (Some(signal), Some(_)) if signal_number(&signal).is_none() => {
+                warn!("invalid hook, unknown signal: {}", signal);
+                None
+            }
+            (Some(signal), Some(pidfile)) => Some(Self::Signal {
+                signal,
+                pidfile: pidfile.to_string(),
+            }),
+            _ => {
+                warn!(
+                    "invalid hook, expected a command or signal and pidfile: {:?}",
+                    val
+                );
+                None
+            }
+        }
+    }
+
+    pub fn run(&self) -> Result<(), String> {
+        let cmd = match self {
+            Self::Command(cmd) => cmd,
+            Self::Signal { signal, pidfile } => return Self::kill(signal, pidfile),
+        };
+        let output = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .output()
+            .map_err(|e| format!("unable to run {}: {}", self, e))?;
+
+        if output.status.success() {
+            Ok(())
+        } else {
+            Err(format!(
+                "{} failed with {}: {}",
+                self,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+        }
+    }
+
+    fn kill(signal: &str, pidfile: &str) -> Result<(), String> {
+        let pid = fs::read_to_string(pidfile)
+            .map_err(|e| format!("unable to read {}: {}", pidfile, e))?;
+        let pid: libc::pid_t = match pid.trim().parse() {
+            Ok(pid) if pid > 0 => pid,
+            Ok(pid) => return Err(format!("invalid pid in {}: {}", pidfile, pid)),
+            Err(e) => return Err(format!("invalid pid in {}: {}", pidfile, e)),
+        };
+        let sig = signal_number(signal).ok_or_else(|| format!("unknown signal {}", signal))?;
+        if unsafe { libc::kill(pid, sig) } != 0 {
+            return Err(format!(
+                "unable to send SIG{} to {} from {}: {}",
+                signal,
+                pid,
+                pidfile,
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,19 @@
-use crate::ipnet::{ToMac, TryInNet, TryToMac};
-use crate::network::InterfaceNetwork;
+use crate::check::{Diagnostic, YamlPath};
+use crate::config::is_settings_key;
+use crate::error::{self, Error};
+use crate::ipnet::{TryInNet, TryToMac};
+use crate::network::{AddressPolicy, InterfaceNetwork, Ipv6Mode};
+use crate::range::{substitute, HostRange};
 use ipnetwork::IpNetwork;
+use itertools::{Either, Itertools};
 use log::warn;
 use pnet::datalink::MacAddr;
+use serde::Serialize;
 use serde_yaml::{Mapping, Value};
-use std::convert::TryFrom;
-use std::net::IpAddr;
+use std::collections::HashMap;
+use std::fmt;
+use std::iter;
+use std::net::{IpAddr, Ipv4Addr};
 
 pub struct Host {
     pub name: String,
@@ -14,47 +22,207 @@ pub struct Host {
 
 impl Host {
     pub fn new(name: String, opts: Value) -> Self {
-        Self {
-            name,
-            opts: Opt::opts_from_vals(opts),
+        let mut found = Vec::new();
+        let opts = Opt::opts_from_vals(opts, &YamlPath::default(), &mut found);
+        for d in found {
+            warn!("{}", d.error);
         }
+        Self { name, opts }
     }
 
+    /// The hosts of a selector. What can't be read is warned about and left
+    /// out.
     pub fn new_hosts(val: Value) -> impl Iterator<Item = Self> {
-        match val {
-            Value::Sequence(seq) => Self::new_hosts_from_seq(seq),
-            _ => Self::new_hosts_from_seq(vec![val]),
-        }
+        Self::parse_hosts(val, YamlPath::default())
+            .filter_map(|h| h.map_err(|d| warn!("{}", d.error)).ok())
     }
 
-    fn new_hosts_from_seq(seq: serde_yaml::Sequence) -> impl Iterator<Item = Self> {
-        seq.into_iter()
-            .filter_map(|v| match v {
-                Value::Mapping(map) => Some(Self::new_hosts_from_map(map)),
-                _ => {
-                    warn!("invalid host map: {:?}", v);
-                    None
-                }
-            })
-            .flatten()
+    /// The hosts of a selector, which is at `path` in the config. What can't
+    /// be read comes as a diagnostic before the host it was left out of.
+    pub fn parse_hosts(
+        val: Value,
+        path: YamlPath,
+    ) -> impl Iterator<Item = Result<Self, Diagnostic>> {
+        let maps = match val {
+            Value::Sequence(seq) => seq
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| (path.index(i), v))
+                .collect(),
+            _ => vec![(path, val)],
+        };
+        maps.into_iter().flat_map(|(path, v)| match v {
+            Value::Mapping(map) => Either::Left(Self::parse_host_map(map, path)),
+            _ => Either::Right(iter::once(Err(Diagnostic::new(
+                path,
+                Error::InvalidHostMap(v),
+            )))),
+        })
     }
 
-    fn new_hosts_from_map(map: Mapping) -> impl Iterator<Item = Self> {
-        map.into_iter().filter_map(|(k, v)| match k {
-            Value::String(name) => Some(Self::new(name, v)),
-            _ => {
-                warn!("invalid host name: {:?}", k);
-                None
+    // A name with a range, like `node[01-40]`, gives a host per number of
+    // the range.
+    fn parse_host_map(
+        map: Mapping,
+        path: YamlPath,
+    ) -> impl Iterator<Item = Result<Self, Diagnostic>> {
+        map.into_iter().enumerate().flat_map(move |(i, (k, v))| {
+            let path = path.key(i, &k);
+            let name = match k {
+                Value::String(name) => name,
+                _ => {
+                    let e = Diagnostic::new(path, Error::InvalidHostName(k));
+                    return Either::Left(vec![Err(e)].into_iter());
+                }
+            };
+            match HostRange::parse(&name) {
+                Ok(Some(range)) => Either::Right(
+                    range
+                        .hosts()
+                        .flat_map(move |(n, name)| Self::parse(name, substitute(&v, n), &path)),
+                ),
+                Ok(None) => Either::Left(Self::parse(name, v, &path).into_iter()),
+                Err(e) => Either::Left(vec![Err(Diagnostic::new(path, e))].into_iter()),
             }
         })
     }
 
+    // The host, after the diagnostics of the options it was made without.
+    fn parse(name: String, opts: Value, path: &YamlPath) -> Vec<Result<Self, Diagnostic>> {
+        let mut found = Vec::new();
+        let opts = Opt::opts_from_vals(opts, path, &mut found);
+        found
+            .into_iter()
+            .map(Err)
+            .chain(iter::once(Ok(Self { name, opts })))
+            .collect()
+    }
+
+    fn context(&self, ipv4: Option<Ipv4Addr>) -> HostContext<'_> {
+        HostContext {
+            name: &self.name,
+            ipv4,
+        }
+    }
+
     pub fn get_mac(&self, net: &InterfaceNetwork) -> Option<MacAddr> {
-        Opt::get_mac(&self.opts, net)
+        Opt::get_mac(&self.opts, net, &self.context(None))
+    }
+
+    /// The ip address of the host in the network. `ipv4-embedded` addresses
+    /// are made from the address of the host in `ipv4s`.
+    pub fn get_ip(&self, net: &InterfaceNetwork, ipv4s: &Ipv4Addrs) -> Option<IpAddr> {
+        let ipv4 = ipv4s.get(&self.name, net);
+        for c in Opt::ip_candidates(&self.opts, net, &self.context(ipv4)) {
+            if let Some(note) = &c.note {
+                warn!("{} on {}: {}", self.name, net.cidr(), note);
+            }
+            if c.value.is_some() {
+                return c.value;
+            }
+        }
+        if ipv4.is_none()
+            && net.network.is_ipv6()
+            && self.ipv6_mode(net) == Some(Ipv6Mode::Ipv4Embedded)
+        {
+            warn!(
+                "{} on {}: no ipv4 address on the same interface to embed",
+                self.name,
+                net.cidr()
+            );
+        }
+        None
+    }
+
+    /// Finds what `new_hosts` would warn about or skip in `val`. The hosts
+    /// of a range share their options, so their problems are listed once.
+    pub fn check_hosts(val: &Value, path: &YamlPath, found: &mut Vec<Diagnostic>) {
+        found.extend(
+            Self::parse_hosts(val.clone(), path.clone())
+                .filter_map(Result::err)
+                .unique_by(Diagnostic::to_string),
+        );
+    }
+
+    // The ipv6 mode of the host in the network, its own or the network's.
+    fn ipv6_mode(&self, net: &InterfaceNetwork) -> Option<Ipv6Mode> {
+        self.opts
+            .iter()
+            .find_map(Opt::ipv6_mode)
+            .or(net.numbering.ipv6_mode)
+    }
+
+    /// Fails when the host asks for a stable privacy address in the network
+    /// but there is no secret to make it from.
+    pub fn check_secret(&self, net: &InterfaceNetwork) -> Result<(), Error> {
+        if net.network.is_ipv6()
+            && net.secret.is_none()
+            && self.ipv6_mode(net) == Some(Ipv6Mode::StablePrivacy)
+        {
+            return Err(Error::MissingSecret(self.name.clone()));
+        }
+        Ok(())
+    }
+
+    /// Shows how `get_mac` picks the mac address for the network.
+    pub fn explain_mac(&self, net: &InterfaceNetwork) -> Choice {
+        Opt::mac_candidates(&self.opts, net, &self.context(None)).into()
+    }
+
+    /// Shows how `get_ip` picks the ip address in the network.
+    pub fn explain_ip(&self, net: &InterfaceNetwork, ipv4s: &Ipv4Addrs) -> Choice {
+        let ipv4 = ipv4s.get(&self.name, net);
+        Opt::ip_candidates(&self.opts, net, &self.context(ipv4)).into()
+    }
+}
+
+/// The ipv4 address each host gets on each interface, which its
+/// `ipv4-embedded` addresses on the same interface are made from. When a
+/// host gets several, the first one in the config is used.
+#[derive(Default)]
+pub struct Ipv4Addrs(HashMap<(String, String), Ipv4Addr>);
+
+impl Ipv4Addrs {
+    /// Resolves the ipv4 addresses of the hosts in a config, selecting from
+    /// `networks`.
+    pub fn from_val(val: &Value, networks: &[InterfaceNetwork]) -> Self {
+        let mut addrs = Self::default();
+        let maps = match val {
+            Value::Sequence(seq) => seq.iter().collect(),
+            _ => vec![val],
+        };
+        for (k, v) in maps
+            .into_iter()
+            .filter_map(Value::as_mapping)
+            .flat_map(|map| map.iter().filter(|(k, _)| !is_settings_key(k)))
+        {
+            let nets: Vec<_> = InterfaceNetwork::filter_networks(networks, k)
+                .into_iter()
+                .filter(|n| n.network.is_ipv4())
+                .collect();
+            if nets.is_empty() {
+                continue;
+            }
+            for host in Host::parse_hosts(v.clone(), YamlPath::default()).filter_map(Result::ok) {
+                for net in &nets {
+                    let ip = Opt::ip_candidates(&host.opts, net, &host.context(None))
+                        .into_iter()
+                        .find_map(|c| c.value);
+                    if let (Some(IpAddr::V4(ip)), Some(iface)) = (ip, &net.iface) {
+                        addrs
+                            .0
+                            .entry((host.name.clone(), iface.name.clone()))
+                            .or_insert(ip);
+                    }
+                }
+            }
+        }
+        addrs
     }
 
-    pub fn get_ip(&self, net: &InterfaceNetwork) -> Option<IpAddr> {
-        Opt::get_ip(&self.opts, net)
+    fn get(&self, host: &str, net: &InterfaceNetwork) -> Option<Ipv4Addr> {
+        let iface = net.iface.as_ref()?;
+        self.0.get(&(host.to_string(), iface.name.clone())).copied()
     }
 }
 
@@ -64,6 +232,7 @@ pub enum Opt {
     IpNet(IpNetwork),
     Int(u64),
     Iface,
+    Mode(Ipv6Mode),
 }
 
 pub enum Label {
@@ -73,64 +242,138 @@ pub enum Label {
     Ip(Vec<Opt>),
 }
 
-impl TryFrom<(Value, Value)> for Label {
-    type Error = ();
-    fn try_from((k, v): (Value, Value)) -> Result<Self, Self::Error> {
-        if let Some(s) = k.as_str() {
-            match s.to_lowercase().as_ref() {
-                "mac" => Ok(Self::Mac(Opt::opts_from_vals(v))),
-                "ip4" | "ipv4" => Ok(Self::Ipv4(Opt::opts_from_vals(v))),
-                "ip6" | "ipv6" => Ok(Self::Ipv6(Opt::opts_from_vals(v))),
-                "ip" => Ok(Self::Ip(Opt::opts_from_vals(v))),
-                _ => {
-                    warn!("unknown label key: {}", s);
-                    Err(())
-                }
-            }
-        } else {
-            warn!("unknown label key: {:?}", k);
-            Err(())
+impl Label {
+    fn from_key(k: &Value) -> Option<fn(Vec<Opt>) -> Self> {
+        match k.as_str()?.to_lowercase().as_ref() {
+            "mac" => Some(Self::Mac),
+            "ip4" | "ipv4" => Some(Self::Ipv4),
+            "ip6" | "ipv6" => Some(Self::Ipv6),
+            "ip" => Some(Self::Ip),
+            _ => None,
+        }
+    }
+
+    /// Reads a labeled entry of the options, like `ip6: 5`, which is at
+    /// `path` in the config.
+    fn parse(
+        k: Value,
+        v: Value,
+        path: &YamlPath,
+        found: &mut Vec<Diagnostic>,
+    ) -> error::Result<Self> {
+        match Self::from_key(&k) {
+            Some(label) => Ok(label(Opt::opts_from_vals(v, path, found))),
+            None => Err(Error::UnknownLabel(k)),
         }
     }
 }
 
+fn is_mode_key(k: &Value) -> bool {
+    k.as_str()
+        .filter(|k| k.eq_ignore_ascii_case("mode"))
+        .is_some()
+}
+
 impl Opt {
-    fn opts_from_vals(val: Value) -> Vec<Opt> {
+    /// Reads the options in `val`, which is at `path` in the config. What
+    /// can't be read is added to `found` and left out. Null gives no options,
+    /// as it is the documented way to skip a label.
+    fn opts_from_vals(val: Value, path: &YamlPath, found: &mut Vec<Diagnostic>) -> Vec<Opt> {
         match val {
-            Value::Sequence(s) => {
-                return s.into_iter().map(Self::opts_from_vals).flatten().collect()
-            }
-            Value::Mapping(m) => {
-                return m
-                    .into_iter()
-                    .filter_map(|(k, v)| Label::try_from((k, v)).map(Self::Labeled).ok())
-                    .collect()
-            }
-            _ => {}
-        };
+            Value::Sequence(s) => s
+                .into_iter()
+                .enumerate()
+                .flat_map(|(i, v)| Self::opts_from_vals(v, &path.index(i), found))
+                .collect(),
+            Value::Mapping(m) => m
+                .into_iter()
+                .enumerate()
+                .filter_map(|(i, (k, v))| {
+                    let path = path.key(i, &k);
+                    Self::from_entry(k, v, &path, found)
+                        .map_err(|e| found.push(Diagnostic::new(path, e)))
+                        .ok()
+                })
+                .collect(),
+            Value::Null => vec![],
+            Value::String(s) if s.eq_ignore_ascii_case("null") => vec![],
+            _ => match Self::from_scalar(&val) {
+                Ok(opt) => vec![opt],
+                Err(e) => {
+                    found.push(Diagnostic::new(path.clone(), e));
+                    vec![]
+                }
+            },
+        }
+    }
+
+    // a label, or the ipv6 mode of the options next to it
+    fn from_entry(
+        k: Value,
+        v: Value,
+        path: &YamlPath,
+        found: &mut Vec<Diagnostic>,
+    ) -> error::Result<Self> {
+        if !is_mode_key(&k) {
+            return Label::parse(k, v, path, found).map(Self::Labeled);
+        }
+        match v.as_str().and_then(|s| s.parse().ok()) {
+            Some(mode) => Ok(Self::Mode(mode)),
+            None => Err(Error::InvalidOption(v)),
+        }
+    }
 
+    fn from_scalar(val: &Value) -> error::Result<Self> {
         if let Some(i) = val.as_u64() {
-            return vec![Self::Int(i)];
+            return Ok(Self::Int(i));
         }
-        if let Some(s) = val.as_str() {
-            if s.to_lowercase() == "iface" {
-                return vec![Self::Iface];
-            }
-            if let Ok(m) = s.parse::<MacAddr>() {
-                return vec![Self::Mac(m)];
-            }
-            if let Ok(ip) = s.parse::<IpNetwork>() {
-                return vec![Self::IpNet(ip)];
-            }
-            if let Ok(i) = s.parse::<u64>() {
-                return vec![Self::Int(i)];
+        let s = match val.as_str() {
+            Some(s) => s,
+            None => return Err(Error::InvalidOption(val.clone())),
+        };
+        if s.to_lowercase() == "iface" {
+            return Ok(Self::Iface);
+        }
+        if let Ok(m) = s.parse::<MacAddr>() {
+            return Ok(Self::Mac(m));
+        }
+        if let Ok(ip) = s.parse::<IpNetwork>() {
+            return Ok(Self::IpNet(ip));
+        }
+        s.parse::<u64>()
+            .map(Self::Int)
+            .map_err(|_| Error::InvalidOption(val.clone()))
+    }
+
+    // The ipv6 mode the option sets for the options next to it. A label of
+    // only a mode is one too, it changes how the other options of the host
+    // become addresses rather than replacing them.
+    fn ipv6_mode(&self) -> Option<Ipv6Mode> {
+        match self {
+            Self::Mode(mode) => Some(*mode),
+            Self::Labeled(Label::Ipv6(opts)) | Self::Labeled(Label::Ip(opts)) => {
+                match opts.as_slice() {
+                    [Self::Mode(mode)] => Some(*mode),
+                    _ => None,
+                }
             }
+            _ => None,
         }
-        warn!("unable to convert val: {:?}", val);
-        vec![]
     }
 
-    fn get_mac(opts: &[Opt], net: &InterfaceNetwork) -> Option<MacAddr> {
+    fn get_mac(opts: &[Opt], net: &InterfaceNetwork, host: &HostContext) -> Option<MacAddr> {
+        Self::mac_candidates(opts, net, host)
+            .into_iter()
+            .find_map(|c| c.value)
+    }
+
+    /// Every option that could give a mac address for the network, in order
+    /// of precedence.
+    fn mac_candidates<'a>(
+        opts: &'a [Opt],
+        net: &InterfaceNetwork,
+        host: &HostContext,
+    ) -> Vec<Candidate<'a, MacAddr>> {
         // try labeled options
         if let Some(o) = opts
             .iter()
@@ -140,49 +383,58 @@ impl Opt {
             })
             .next()
         {
-            return Self::get_mac(o, net);
+            return Candidate::labeled("mac", Self::mac_candidates(o, net, host));
         }
 
-        opts.iter()
-            .filter_map(|o| {
-                // parsed macs
-                match o {
-                    Self::Mac(mac) => Some(*mac),
-                    _ => None,
-                }
-            })
-            .chain(opts.iter().filter_map(|o| {
-                // interfaces
-                match o {
-                    Self::Iface => net.iface.as_ref().and_then(|i| i.mac),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // integers
-                match o {
-                    Self::Int(i) => i.try_to_mac(),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // ipv6 addresses
-                match o {
-                    Self::IpNet(IpNetwork::V6(v6)) => v6.ip().try_to_mac(),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // ipv4 addresses
-                match o {
-                    Self::IpNet(IpNetwork::V4(v4)) => v4.ip().try_to_mac(),
-                    _ => None,
-                }
-            }))
-            .next()
+        let tiers: [Tier<MacAddr>; 5] = [
+            // parsed macs
+            ("mac", |o, _, _| match o {
+                Self::Mac(mac) => Some(Some(*mac)),
+                _ => None,
+            }),
+            // interfaces
+            ("iface", |o, net, _| match o {
+                Self::Iface => Some(net.iface.as_ref().and_then(|i| i.mac)),
+                _ => None,
+            }),
+            // integers
+            ("integer", |o, _, _| match o {
+                Self::Int(i) => Some(i.try_to_mac()),
+                _ => None,
+            }),
+            // ipv6 addresses
+            ("ipv6 address", |o, _, _| match o {
+                Self::IpNet(IpNetwork::V6(v6)) => Some(v6.ip().try_to_mac()),
+                _ => None,
+            }),
+            // ipv4 addresses
+            ("ipv4 address", |o, _, _| match o {
+                Self::IpNet(IpNetwork::V4(v4)) => Some(v4.ip().try_to_mac()),
+                _ => None,
+            }),
+        ];
+        Candidate::from_tiers(&tiers, opts, net, host)
     }
 
-    fn get_ip(opts: &[Opt], net: &InterfaceNetwork) -> Option<IpAddr> {
+    /// Every option that could give an ip address in the network, in order
+    /// of precedence.
+    fn ip_candidates<'a>(
+        opts: &'a [Opt],
+        net: &InterfaceNetwork,
+        host: &HostContext,
+    ) -> Vec<Candidate<'a, IpAddr>> {
+        // a mode next to the options replaces the mode of the network
+        let with_mode;
+        let net = match opts.iter().find_map(Self::ipv6_mode) {
+            Some(mode) => {
+                let mut n = net.clone();
+                n.numbering.ipv6_mode = Some(mode);
+                with_mode = n;
+                &with_mode
+            }
+            None => net,
+        };
+
         if net.network.is_ipv4() {
             // try labeled ipv4 options
             if let Some(o) = opts
@@ -193,7 +445,7 @@ impl Opt {
                 })
                 .next()
             {
-                return Self::get_ip(o, net);
+                return Candidate::labeled("ipv4", Self::ip_candidates(o, net, host));
             }
         }
 
@@ -202,12 +454,12 @@ impl Opt {
             if let Some(o) = opts
                 .iter()
                 .filter_map(|o| match o {
-                    Self::Labeled(Label::Ipv6(ip_opts)) => Some(ip_opts),
+                    Self::Labeled(Label::Ipv6(ip_opts)) if o.ipv6_mode().is_none() => Some(ip_opts),
                     _ => None,
                 })
                 .next()
             {
-                return Self::get_ip(o, net);
+                return Candidate::labeled("ipv6", Self::ip_candidates(o, net, host));
             }
         }
 
@@ -215,69 +467,215 @@ impl Opt {
         if let Some(o) = opts
             .iter()
             .filter_map(|o| match o {
-                Self::Labeled(Label::Ip(ip_opts)) => Some(ip_opts),
+                Self::Labeled(Label::Ip(ip_opts)) if o.ipv6_mode().is_none() => Some(ip_opts),
                 _ => None,
             })
             .next()
         {
-            return Self::get_ip(o, net);
+            return Candidate::labeled("ip", Self::ip_candidates(o, net, host));
         }
 
-        opts.iter()
-            .filter_map(|o| {
-                // parsed ips in same network
-                match o {
-                    Self::IpNet(ip) => {
-                        if net.network.contains(ip.ip()) {
-                            Some(ip.ip())
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
+        let tiers: [Tier<IpAddr>; 7] = [
+            // parsed ips in same network
+            ("ip in network", |o, net, _| match o {
+                Self::IpNet(ip) if net.network.contains(ip.ip()) => Some(Some(ip.ip())),
+                Self::IpNet(_) => Some(None),
+                _ => None,
+            }),
+            // parsed ips in same family
+            ("ip in family", |o, net, _| match o {
+                Self::IpNet(ip) if net.network.is_ipv4() == ip.is_ipv4() => {
+                    Some(ip.ip().try_in_net(&net.network))
                 }
+                Self::IpNet(_) => Some(None),
+                _ => None,
+            }),
+            // interfaces
+            ("iface", |o, net, _| match o {
+                Self::Iface => Some(net.network.ip().try_in_net(&net.network)),
+                _ => None,
+            }),
+            // integers (as mac addresses, unless a base or ipv6 mode says otherwise)
+            ("integer", |o, net, host| match o {
+                Self::Int(_) if net.stable_privacy() => Some(net.stable_privacy_address(host.name)),
+                Self::Int(_) if net.ipv4_embedded() => Some(host.embedded_address(net)),
+                Self::Int(i) => Some(Some(net.host_address(*i))),
+                _ => None,
+            }),
+            // mac addresses
+            ("mac", |o, net, host| match o {
+                Self::Mac(_) if net.stable_privacy() => Some(net.stable_privacy_address(host.name)),
+                Self::Mac(_) if net.ipv4_embedded() => Some(host.embedded_address(net)),
+                Self::Mac(mac) => Some(mac.try_in_net(&net.network)),
+                _ => None,
+            }),
+            // any ip addresses
+            ("any ip", |o, net, _| match o {
+                Self::IpNet(ip) => Some(ip.ip().try_in_net(&net.network)),
+                _ => None,
+            }),
+            // a stable privacy or ipv4-embedded mode on its own
+            ("mode", |o, net, host| match o.ipv6_mode() {
+                Some(_) if net.stable_privacy() => Some(net.stable_privacy_address(host.name)),
+                Some(_) if net.ipv4_embedded() => Some(host.embedded_address(net)),
+                _ => None,
+            }),
+        ];
+        let mut candidates = Candidate::from_tiers(&tiers, opts, net, host);
+        for c in &mut candidates {
+            c.apply_policy(net);
+        }
+        candidates
+    }
+}
+
+impl fmt::Display for Opt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Labeled(label) => {
+                let (key, opts) = match label {
+                    Label::Mac(opts) => ("mac", opts),
+                    Label::Ipv4(opts) => ("ipv4", opts),
+                    Label::Ipv6(opts) => ("ipv6", opts),
+                    Label::Ip(opts) => ("ip", opts),
+                };
+                write!(f, "{}: [{}]", key, opts.iter().join(", "))
+            }
+            Self::Mac(mac) => write!(f, "{}", mac),
+            Self::IpNet(ip) => write!(f, "{}", ip),
+            Self::Int(i) => write!(f, "{}", i),
+            Self::Iface => write!(f, "iface"),
+            Self::Mode(mode) => write!(f, "mode: {}", mode),
+        }
+    }
+}
+
+/// A rule in the precedence of `Opt::get_mac` or `Opt::get_ip`. It returns
+/// `None` for options it does not consider, and `Some(None)` for options it
+/// considers but cannot use in the network.
+type Tier<T> = (
+    &'static str,
+    fn(&Opt, &InterfaceNetwork, &HostContext) -> Option<Option<T>>,
+);
+
+/// What the tiers know of a host besides its options: its name, and the
+/// ipv4 address it got on the interface of the network.
+struct HostContext<'a> {
+    name: &'a str,
+    ipv4: Option<Ipv4Addr>,
+}
+
+impl<'a> HostContext<'a> {
+    fn embedded_address(&self, net: &InterfaceNetwork) -> Option<IpAddr> {
+        net.embedded_address(self.ipv4?)
+    }
+}
+
+struct Candidate<'a, T> {
+    tier: String,
+    opt: &'a Opt,
+    value: Option<T>,
+    // why the value is not a good address in the network
+    note: Option<String>,
+}
+
+impl<'a, T> Candidate<'a, T> {
+    fn from_tiers(
+        tiers: &[Tier<T>],
+        opts: &'a [Opt],
+        net: &InterfaceNetwork,
+        host: &HostContext,
+    ) -> Vec<Self> {
+        tiers
+            .iter()
+            .flat_map(|(tier, f)| {
+                opts.iter().filter_map(move |opt| {
+                    Some(Self {
+                        tier: tier.to_string(),
+                        opt,
+                        value: f(opt, net, host)?,
+                        note: None,
+                    })
+                })
             })
-            .chain(opts.iter().filter_map(|o| {
-                // parsed ips in same family
-                match o {
-                    Self::IpNet(ip) => {
-                        if net.network.is_ipv4() == ip.is_ipv4() {
-                            ip.ip().try_in_net(&net.network)
-                        } else {
-                            None
-                        }
-                    }
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // interfaces
-                match o {
-                    Self::Iface => net.network.ip().try_in_net(&net.network),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // integers (as mac addresses)
-                match o {
-                    Self::Int(i) => i.to_mac().try_in_net(&net.network),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // mac addresses
-                match o {
-                    Self::Mac(mac) => mac.try_in_net(&net.network),
-                    _ => None,
-                }
-            }))
-            .chain(opts.iter().filter_map(|o| {
-                // any ip addresses
-                match o {
-                    Self::IpNet(ip) => ip.ip().try_in_net(&net.network),
-                    _ => None,
-                }
-            }))
-            .next()
+            .collect()
+    }
+
+    fn labeled(label: &str, candidates: Vec<Self>) -> Vec<Self> {
+        candidates
+            .into_iter()
+            .map(|mut c| {
+                c.tier = format!("{} label, {}", label, c.tier);
+                c
+            })
+            .collect()
+    }
+}
+
+impl<'a> Candidate<'a, IpAddr> {
+    /// Checks that the address did not wrap around the host bits of the
+    /// network and is not reserved in it, and applies the policy of the
+    /// network if it did or is. Addresses taken from the interface are what
+    /// was asked for, so they are left alone.
+    fn apply_policy(&mut self, net: &InterfaceNetwork) {
+        let ip = match self.value {
+            Some(ip) if net.policy != AddressPolicy::Allow && !matches!(self.opt, Opt::Iface) => ip,
+            _ => return,
+        };
+        // stable privacy and ipv4-embedded addresses are not made from the
+        // bits of the integer
+        let own_bits = !net.stable_privacy() && !net.ipv4_embedded();
+        let problem = match self.opt {
+            Opt::Int(i) if own_bits && !net.host_fits(*i) => Some("wraps around the host bits"),
+            _ => None,
+        }
+        .or_else(|| net.reserved(ip));
+        if let Some(problem) = problem {
+            if net.policy == AddressPolicy::Refuse {
+                self.value = None;
+                self.note = Some(format!("{} from {} {}, refused", ip, self.opt, problem));
+            } else {
+                self.note = Some(format!("{} from {} {}", ip, self.opt, problem));
+            }
+        }
+    }
+}
+
+/// An option considered while choosing a mac or ip address, with the rule
+/// that considered it and the address it gave, if any.
+#[derive(Serialize)]
+pub struct Considered {
+    pub tier: String,
+    pub opt: String,
+    pub value: Option<String>,
+    pub note: Option<String>,
+}
+
+/// The option chosen for a mac or ip address and every other option that
+/// was considered.
+#[derive(Serialize)]
+pub struct Choice {
+    pub chosen: Option<Considered>,
+    pub skipped: Vec<Considered>,
+}
+
+impl<'a, T: fmt::Display> From<Vec<Candidate<'a, T>>> for Choice {
+    fn from(candidates: Vec<Candidate<'a, T>>) -> Self {
+        let mut chosen = None;
+        let mut skipped = Vec::new();
+        for c in candidates {
+            let considered = Considered {
+                tier: c.tier,
+                opt: c.opt.to_string(),
+                value: c.value.as_ref().map(T::to_string),
+                note: c.note,
+            };
+            if chosen.is_none() && c.value.is_some() {
+                chosen = Some(considered);
+            } else {
+                skipped.push(considered);
+            }
+        }
+        Self { chosen, skipped }
     }
 }
@@ -127,6 +127,67 @@ impl ToEUI64Mac for Ipv6Addr {
     }
 }
 
+pub trait ToReverseName {
+    fn to_reverse_name(&self) -> String;
+    fn to_reverse_zone(&self, prefix: u8) -> String;
+}
+
+impl ToReverseName for Ipv4Addr {
+    fn to_reverse_name(&self) -> String {
+        self.to_reverse_zone(32)
+    }
+
+    fn to_reverse_zone(&self, prefix: u8) -> String {
+        // in-addr.arpa zones are delegated on octet boundaries
+        let octets = usize::from(prefix.min(32) / 8);
+        self.octets()[..octets]
+            .iter()
+            .rev()
+            .map(|o| o.to_string())
+            .chain(std::iter::once("in-addr.arpa".to_string()))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl ToReverseName for Ipv6Addr {
+    fn to_reverse_name(&self) -> String {
+        self.to_reverse_zone(128)
+    }
+
+    fn to_reverse_zone(&self, prefix: u8) -> String {
+        // ip6.arpa zones are delegated on nibble boundaries
+        let nibbles = usize::from(prefix.min(128) / 4);
+        self.octets()
+            .iter()
+            .flat_map(|o| vec![o >> 4, o & 0x0f])
+            .take(nibbles)
+            .collect::<Vec<_>>()
+            .iter()
+            .rev()
+            .map(|n| format!("{:x}", n))
+            .chain(std::iter::once("ip6.arpa".to_string()))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+impl ToReverseName for IpAddr {
+    fn to_reverse_name(&self) -> String {
+        match self {
+            Self::V4(v4) => v4.to_reverse_name(),
+            Self::V6(v6) => v6.to_reverse_name(),
+        }
+    }
+
+    fn to_reverse_zone(&self, prefix: u8) -> String {
+        match self {
+            Self::V4(v4) => v4.to_reverse_zone(prefix),
+            Self::V6(v6) => v6.to_reverse_zone(prefix),
+        }
+    }
+}
+
 pub trait InNet<N, A> {
     fn in_net(&self, net: &N) -> A;
 }
@@ -220,6 +281,30 @@ impl InNet<IpNetwork, IpAddr> for u64 {
     }
 }
 
+/// Whether `in_net` places the value in the host bits of the network
+/// without dropping any of its bits, which would make it wrap around.
+pub trait FitsInNet {
+    fn fits_in_net(&self, net: &IpNetwork) -> bool;
+}
+
+impl FitsInNet for MacAddr {
+    fn fits_in_net(&self, net: &IpNetwork) -> bool {
+        match net {
+            IpNetwork::V6(v6net) => {
+                u128::from(self.to_eui64_ipv6()) & u128::from(v6net.mask()) == 0
+            }
+            IpNetwork::V4(v4net) => u32::from(self.to_ipv4()) & u32::from(v4net.mask()) == 0,
+        }
+    }
+}
+
+impl FitsInNet for u64 {
+    fn fits_in_net(&self, net: &IpNetwork) -> bool {
+        // to_mac keeps the low 48 bits
+        *self >> 48 == 0 && self.to_mac().fits_in_net(net)
+    }
+}
+
 fn int_in_net<
     I: Clone + std::ops::BitAnd<Output = I> + std::ops::Not<Output = I> + std::ops::BitOr<Output = I>,
 >(
@@ -229,3 +314,43 @@ fn int_in_net<
 ) -> I {
     (net & mask.clone()) | (ip & !mask)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv4_reverse_names() {
+        let ip: Ipv4Addr = "192.168.1.10".parse().unwrap();
+        assert_eq!(ip.to_reverse_name(), "10.1.168.192.in-addr.arpa");
+        assert_eq!(ip.to_reverse_zone(24), "1.168.192.in-addr.arpa");
+        assert_eq!(ip.to_reverse_zone(16), "168.192.in-addr.arpa");
+        // zones are cut at octet boundaries
+        assert_eq!(ip.to_reverse_zone(20), "168.192.in-addr.arpa");
+        assert_eq!(ip.to_reverse_zone(0), "in-addr.arpa");
+    }
+
+    #[test]
+    fn ipv6_reverse_names() {
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(
+            ip.to_reverse_name(),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+        assert_eq!(
+            ip.to_reverse_zone(64),
+            "0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+        // zones are cut at nibble boundaries
+        assert_eq!(ip.to_reverse_zone(50), "0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa");
+    }
+
+    #[test]
+    fn ip_addr_reverse_names() {
+        let v4: IpAddr = "10.0.0.1".parse().unwrap();
+        let v6: IpAddr = "::1".parse().unwrap();
+        assert_eq!(v4.to_reverse_zone(8), "10.in-addr.arpa");
+        assert!(v6.to_reverse_name().starts_with("1.0.0.0."));
+        assert!(v6.to_reverse_name().ends_with(".0.ip6.arpa"));
+    }
+}
@@ -1,5 +1,18 @@
+pub mod check;
+pub mod collision;
+pub mod config;
 pub mod entry;
+pub mod error;
+pub mod explain;
+pub mod hook;
 pub mod hosts;
 pub mod ipnet;
 pub mod network;
-pub mod chain;
\ No newline at end of file
+pub mod output;
+pub mod range;
+pub mod chain;
+pub mod template;
+pub mod watch;
+pub mod zone;
+
+pub use error::Error;
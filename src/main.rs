@@ -1,13 +1,30 @@
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use hostgen::chain::IntoFlatEntryIterator;
+use hostgen::check::{check_config, check_val};
+use hostgen::collision::{resolve_collisions, COLLISION_POLICIES};
+use hostgen::config::{self, settings_section};
 use hostgen::entry::entries_from_dnsmasq_leases;
-use hostgen::entry::{entries_from_val, EntryIterator, EntryIteratorFrom};
+use hostgen::entry::{
+    entries_from_val_with, entries_with_source, Entry, EntryIteratorFrom, Source,
+};
+use hostgen::explain::explain_val_with;
+use hostgen::network::{
+    InterfaceNetwork, Ipv6Mode, LiveNetworks, NetworkProvider, SnapshotNetworks, StaticNetworks,
+    ADDRESS_POLICIES, IPV6_MODES, MIN_SECRET_LEN,
+};
+use hostgen::output::{Format, Output, OutputOptions, FORMATS};
+use hostgen::template::Template;
+use hostgen::watch::watch;
+use hostgen::zone::ZoneHeader;
+use hostgen::Error;
 use itertools::Itertools;
 use log::error;
 use serde_yaml::Value;
-use std::fs::File;
+use std::io;
 use std::io::BufRead;
-use std::io::{self};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     env_logger::init();
@@ -23,6 +40,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .help("config file")
                 .default_value("hosts.yaml")
                 .takes_value(true)
+                .global(true)
                 .multiple(true),
         )
         .arg(
@@ -32,38 +50,460 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 .value_name("FILE")
                 .help("dnsmasq leases file")
                 .takes_value(true)
+                .global(true)
                 .multiple(true),
         )
         .arg(
             Arg::with_name("output")
                 .short("o")
                 .long("output")
-                .value_name("FILE")
-                .help("output file")
-                .takes_value(true),
+                .value_name("[FORMAT=]FILE")
+                .help("output file, prefixed with its format to write several outputs")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
         )
         .arg(
             Arg::with_name("format")
                 .short("f")
                 .long("format")
                 .takes_value(true)
-                .required(true)
-                .possible_values(&["dnsmasq", "zone", "env"]),
+                .possible_values(FORMATS),
+        )
+        .arg(
+            Arg::with_name("exit-unchanged")
+                .long("exit-unchanged")
+                .help("exit with status 2 when no output changed"),
+        )
+        .arg(
+            Arg::with_name("watch")
+                .long("watch")
+                .help("keep running and regenerate when addresses or input files change"),
+        )
+        .arg(
+            Arg::with_name("debounce")
+                .long("debounce")
+                .value_name("MILLISECONDS")
+                .help("time to wait for further changes before regenerating in watch mode")
+                .default_value("1000")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("networks")
+                .long("networks")
+                .value_name("SOURCE")
+                .help("networks to select from: live interfaces, those declared in the config, or both")
+                .takes_value(true)
+                .global(true)
+                .possible_values(&["live", "declared", "merged"])
+                .default_value("merged"),
+        )
+        .arg(
+            Arg::with_name("interfaces-from")
+                .long("interfaces-from")
+                .value_name("FILE")
+                .help("use interfaces from a snapshot instead of the live ones")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("address-policy")
+                .long("address-policy")
+                .value_name("POLICY")
+                .help("what to do with addresses that wrap around or land on a reserved address of their network")
+                .takes_value(true)
+                .global(true)
+                .possible_values(ADDRESS_POLICIES)
+                .default_value("warn"),
+        )
+        .arg(
+            Arg::with_name("ipv6-mode")
+                .long("ipv6-mode")
+                .value_name("MODE")
+                .help("how the host bits of ipv6 addresses are made from integers and mac addresses")
+                .takes_value(true)
+                .global(true)
+                .possible_values(IPV6_MODES)
+                .default_value("eui64"),
+        )
+        .arg(
+            Arg::with_name("secret-file")
+                .long("secret-file")
+                .value_name("FILE")
+                .help("file with the secret key of stable-privacy ipv6 addresses")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("collisions")
+                .long("collisions")
+                .value_name("POLICY")
+                .help("what to do when distinct hosts get the same ip or mac address")
+                .takes_value(true)
+                .possible_values(COLLISION_POLICIES)
+                .default_value("warn"),
+        )
+        .arg(
+            Arg::with_name("domain")
+                .long("domain")
+                .value_name("DOMAIN")
+                .help("domain appended to host names in ptr, hosts and unbound records")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("hosts-group")
+                .long("hosts-group")
+                .help("keep all addresses of a host together in hosts output"),
+        )
+        .arg(
+            Arg::with_name("hosts-localhost")
+                .long("hosts-localhost")
+                .help("start hosts output with localhost entries"),
+        )
+        .arg(
+            Arg::with_name("unbound-local-zone")
+                .long("unbound-local-zone")
+                .value_name("TYPE")
+                .help("declare a local-zone of this type for the domain in unbound output")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("token-dev")
+                .long("token-dev")
+                .value_name("DEV")
+                .help("device the hosts set their token on in ip-token output, eth0 when not given")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("template")
+                .long("template")
+                .value_name("FILE")
+                .help("yaml file with the entry, header and footer of template output")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("origin")
+                .long("origin")
+                .value_name("DOMAIN")
+                .help("$ORIGIN of the generated zone file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ttl")
+                .long("ttl")
+                .value_name("SECONDS")
+                .help("default $TTL of the generated zone file")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("ns")
+                .long("ns")
+                .value_name("NAME")
+                .help("name server of the generated zone file")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot-interfaces")
+                .about("Writes the interfaces of this machine to a file for --interfaces-from")
+                .arg(
+                    Arg::with_name("output")
+                        .short("o")
+                        .long("output")
+                        .value_name("FILE")
+                        .help("output file")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["yaml", "json"])
+                        .default_value("yaml"),
+                ),
+        )
+        .arg(
+            Arg::with_name("strict")
+                .long("strict")
+                .help("refuse to generate when hostgen check finds problems in the config"),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks the configs for problems and exits with status 1 if any are found"),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Shows how the address of each host was chosen on each network")
+                .arg(
+                    Arg::with_name("format")
+                        .short("f")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(&["text", "json"])
+                        .default_value("text"),
+                )
+                .arg(
+                    Arg::with_name("host")
+                        .value_name("HOST")
+                        .help("only explain these hosts")
+                        .multiple(true),
+                ),
         )
         .get_matches();
 
-    let entries = ordered_values_of(&matches, "config", "leases")
+    if let Some(matches) = matches.subcommand_matches("snapshot-interfaces") {
+        let snapshot = SnapshotNetworks::capture();
+        let (format, mut data) = match matches.value_of("format") {
+            Some("json") => (Format::Json, snapshot.to_json()?),
+            _ => (Format::Yaml, snapshot.to_yaml()?),
+        };
+        data.push('\n');
+        let output = Output::new(format, matches.value_of("output").map(String::from));
+        output.write(data.as_bytes())?;
+        return Ok(());
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("check") {
+        let problems = check_configs(sub_matches)?;
+        for p in &problems {
+            println!("{}", p);
+        }
+        if !problems.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(sub_matches) = matches.subcommand_matches("explain") {
+        return explain(sub_matches);
+    }
+
+    if matches.is_present("watch") {
+        let mut files: Vec<PathBuf> = [
+            "config",
+            "leases",
+            "template",
+            "interfaces-from",
+            "secret-file",
+        ]
+        .iter()
+        .flat_map(|arg| matches.values_of(arg).unwrap_or_default())
+        .map(PathBuf::from)
+        .collect();
+        // templates of the outputs sections, as named when watching started
+        for (v, data) in read_inputs(&matches) {
+            if let Some(outputs) = data.as_ref().and_then(|d| settings_section(d, "outputs")) {
+                files.extend(Output::template_paths(outputs, &config_dir(v)));
+            }
+        }
+        let debounce =
+            Duration::from_millis(matches.value_of("debounce").unwrap_or("1000").parse()?);
+        let run = || {
+            if let Err(e) = generate(&matches) {
+                error!("{}", e);
+            }
+        };
+        run();
+        watch(files, debounce, run)?;
+        return Ok(());
+    }
+
+    let changed = generate(&matches)?;
+    if !changed && matches.is_present("exit-unchanged") {
+        std::process::exit(2);
+    }
+
+    Ok(())
+}
+
+type Inputs<'a> = Vec<(&'a str, Option<Value>)>;
+
+/// Reads the config and leases arguments in the order they were given.
+/// Leases files are read later, so they have no data yet.
+fn read_inputs<'a>(matches: &'a clap::ArgMatches) -> Inputs<'a> {
+    ordered_values_of(matches, "config", "leases")
         .filter_map(|(a, v)| match a {
             "config" => {
-                let f = std::fs::File::open(v)
-                    .on_err(|e| error!("unable to read {}: {}", v, e))
-                    .ok()?;
-                let data: Value = serde_yaml::from_reader(f)
-                    .on_err(|e| error!("unable to parse yaml in {}: {}", v, e))
-                    .ok()?;
-                Some(EntryIteratorFrom::Val(entries_from_val(data)))
+                let data = config::load(v).on_err(|e| error!("{}: {}", v, e)).ok()?;
+                Some((v, Some(data)))
+            }
+            "leases" => Some((v, None)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Resolves the networks hosts are selected from, according to the
+/// `--networks` and `--interfaces-from` arguments, with their address
+/// policies.
+fn select_networks(
+    matches: &clap::ArgMatches,
+    inputs: &[(&str, Option<Value>)],
+) -> Result<StaticNetworks, Box<dyn std::error::Error>> {
+    let declared = inputs
+        .iter()
+        .filter_map(|(_, data)| data.as_ref())
+        .filter_map(|data| settings_section(data, "networks"))
+        .flat_map(InterfaceNetwork::from_val)
+        .collect();
+    let live: Box<dyn NetworkProvider> = match matches.value_of("interfaces-from") {
+        Some(path) => Box::new(SnapshotNetworks::load(path)?),
+        None => Box::new(LiveNetworks),
+    };
+    let mut networks = match matches.value_of("networks") {
+        Some("declared") => declared,
+        Some("live") => live.networks(),
+        _ => InterfaceNetwork::merged(live.networks(), declared),
+    };
+    networks.extend(InterfaceNetwork::none_networks());
+
+    let policy = matches
+        .value_of("address-policy")
+        .unwrap_or("warn")
+        .parse()?;
+    let ipv6_mode: Ipv6Mode = matches.value_of("ipv6-mode").unwrap_or("eui64").parse()?;
+    let secret: Option<Rc<[u8]>> = match matches.value_of("secret-file") {
+        Some(path) => {
+            let secret = std::fs::read(path)?;
+            if secret.len() < MIN_SECRET_LEN {
+                return Err(format!(
+                    "the secret in {} is shorter than {} bytes",
+                    path, MIN_SECRET_LEN
+                )
+                .into());
+            }
+            Some(secret.into())
+        }
+        None if ipv6_mode == Ipv6Mode::StablePrivacy => {
+            return Err("stable-privacy ipv6 mode needs a --secret-file".into())
+        }
+        None => None,
+    };
+    for n in &mut networks {
+        n.policy = policy;
+        n.numbering.ipv6_mode = Some(ipv6_mode);
+        n.secret = secret.clone();
+    }
+    for section in inputs
+        .iter()
+        .filter_map(|(_, data)| data.as_ref())
+        .filter_map(|data| settings_section(data, "address_policy"))
+    {
+        networks = InterfaceNetwork::with_policies(networks, section);
+    }
+    Ok(StaticNetworks(networks))
+}
+
+/// Checks every config, returning its problems prefixed with the file.
+fn check_configs(matches: &clap::ArgMatches) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let texts = matches
+        .values_of("config")
+        .unwrap_or_default()
+        .map(|path| (path, std::fs::read_to_string(path)))
+        .collect::<Vec<_>>();
+    // parse errors are reported by check_config, unreadable files below
+    let inputs = texts
+        .iter()
+        .filter_map(|(path, text)| {
+            let text = text.as_ref().ok()?;
+            Some((*path, Some(serde_yaml::from_str(text).ok()?)))
+        })
+        .collect::<Vec<_>>();
+    let networks = select_networks(matches, &inputs)?;
+
+    let mut problems = Vec::new();
+    for (path, text) in texts {
+        match text {
+            Ok(text) => {
+                problems.extend(check_config(&text, &networks).into_iter().map(
+                    |p| match p.location {
+                        Some(_) => format!("{}:{}", path, p),
+                        None => format!("{}: {}", path, p),
+                    },
+                ))
+            }
+            Err(e) => problems.push(format!("{}: {}", path, Error::from(e))),
+        }
+    }
+    Ok(problems)
+}
+
+/// Prints how each host got its addresses instead of generating outputs.
+fn explain(matches: &clap::ArgMatches) -> Result<(), Box<dyn std::error::Error>> {
+    let inputs = read_inputs(matches);
+    let networks = select_networks(matches, &inputs)?;
+    let hosts = matches.values_of("host").map(|h| h.collect::<Vec<_>>());
+    let explanations = inputs
+        .iter()
+        .filter_map(|(_, data)| data.as_ref())
+        .flat_map(|data| explain_val_with(data, &networks))
+        .filter(|e| {
+            hosts
+                .as_ref()
+                .filter(|h| !h.contains(&e.host.as_str()))
+                .is_none()
+        })
+        .collect::<Vec<_>>();
+    match matches.value_of("format") {
+        Some("json") => println!("{}", serde_json::to_string_pretty(&explanations)?),
+        _ => {
+            for e in explanations {
+                print!("{}", e);
             }
-            "leases" => {
+        }
+    }
+    Ok(())
+}
+
+/// Runs the whole pipeline once and returns whether any output changed.
+fn generate(matches: &clap::ArgMatches) -> Result<bool, Box<dyn std::error::Error>> {
+    if matches.is_present("strict") {
+        let problems = check_configs(matches)?;
+        for p in &problems {
+            error!("{}", p);
+        }
+        if !problems.is_empty() {
+            return Err(format!("{} problems found in the configs", problems.len()).into());
+        }
+    }
+
+    let inputs = read_inputs(matches);
+    let configs = || inputs.iter().filter_map(|(_, data)| data.as_ref());
+    let zone = configs()
+        .filter_map(|data| settings_section(data, "zone"))
+        .map(ZoneHeader::from_val)
+        .next();
+    let config_outputs = inputs
+        .iter()
+        .filter_map(|(v, data)| Some((v, settings_section(data.as_ref()?, "outputs")?)))
+        .map(|(v, outputs)| Output::outputs_from_val(outputs, &config_dir(v)))
+        .next();
+    let networks = select_networks(matches, &inputs)?;
+    // stable privacy addresses can't be made at all without a secret
+    for (path, data) in inputs
+        .iter()
+        .filter_map(|(v, data)| Some((v, data.as_ref()?)))
+    {
+        let mut found = Vec::new();
+        check_val(data, &networks.0, &mut found);
+        if let Some(d) = found
+            .iter()
+            .find(|d| matches!(d.error, Error::MissingSecret(_)))
+        {
+            return Err(format!("{}: {}", path, d).into());
+        }
+    }
+
+    let sources = inputs
+        .into_iter()
+        .filter_map(|(v, data)| match data {
+            Some(data) => Some(EntryIteratorFrom::Val(entries_with_source(
+                entries_from_val_with(data, &networks),
+                Source::Config(v.to_string()),
+            ))),
+            None => {
                 let f = std::fs::File::open(v)
                     .on_err(|e| error!("unable to read {}: {}", v, e))
                     .ok()?;
@@ -71,33 +511,116 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     l.on_err(|e| error!("error readling line in {}: {}", v, e))
                         .ok()
                 });
-                Some(EntryIteratorFrom::DnsMasq(entries_from_dnsmasq_leases(
-                    lines,
+                Some(EntryIteratorFrom::DnsMasq(entries_with_source(
+                    entries_from_dnsmasq_leases(lines),
+                    Source::Leases(v.to_string()),
                 )))
             }
-            _ => None,
         })
-        .flatten_entries();
-
-    let entries = {
-        match matches.value_of("format") {
-            Some("dnsmasq") => entries.as_dnsmasq_reservations(),
-            Some("zone") => entries.as_zone_records(),
-            Some("env") => entries.as_env_vars(),
-            _ => return Ok(()),
+        .map(Iterator::collect)
+        .collect::<Vec<Vec<Entry>>>();
+    let policy = matches.value_of("collisions").unwrap_or("warn").parse()?;
+    let sources = resolve_collisions(sources, policy).map_err(|collisions| {
+        for c in &collisions {
+            error!("{}", c);
         }
+        format!("{} address collisions between hosts", collisions.len())
+    })?;
+    let entries: Vec<Entry> = sources
+        .into_iter()
+        .map(Vec::into_iter)
+        .flatten_entries()
+        .collect();
+
+    let mut zone = zone.unwrap_or_default();
+    if let Some(origin) = matches.value_of("origin") {
+        zone.origin = Some(origin.to_string());
+    }
+    if let Some(ttl) = matches.value_of("ttl") {
+        zone.ttl = Some(ttl.parse()?);
+    }
+    if let Some(ns) = matches.values_of("ns") {
+        zone.ns = ns.map(String::from).collect();
+    }
+    let domain = matches
+        .value_of("domain")
+        .map(String::from)
+        .or_else(|| zone.origin.clone());
+
+    let template = match matches.value_of("template") {
+        Some(t) => Some(Template::load(t).ok_or("invalid template")?),
+        None => None,
     };
 
-    if let Some(output) = matches.value_of("output") {
-        let mut writer = File::create(output)?;
-        entries.write(&mut writer)?;
-    } else {
-        let stdout = io::stdout();
-        let mut writer = stdout.lock();
-        entries.write(&mut writer)?;
+    let format = matches.value_of("format");
+    let mut outputs = Vec::new();
+    for o in matches.values_of("output").unwrap_or_default() {
+        match (o.parse::<Output>(), format) {
+            (Ok(output), _) if o.contains('=') => outputs.push(output),
+            (_, Some(format)) => outputs.push(Output::new(format.parse()?, Some(o.to_string()))),
+            // a bare format is written to stdout
+            (Ok(output), None) => outputs.push(output),
+            (Err(e), None) => return Err(e.into()),
+        }
+    }
+    if outputs.is_empty() {
+        if let Some(format) = format {
+            outputs.push(Output::new(format.parse()?, None));
+        }
+    }
+    if outputs.is_empty() {
+        outputs = config_outputs.unwrap_or_default();
+    }
+    if outputs.is_empty() {
+        return Err("no output format given".into());
     }
 
-    Ok(())
+    let opts = OutputOptions {
+        domain,
+        zone,
+        hosts_group: matches.is_present("hosts-group"),
+        hosts_localhost: matches.is_present("hosts-localhost"),
+        unbound_local_zone: matches.value_of("unbound-local-zone").map(String::from),
+        token_dev: matches.value_of("token-dev").map(String::from),
+        template,
+    };
+
+    let mut failed = false;
+    let mut changed = false;
+    for output in &outputs {
+        let name = output.path.as_deref().unwrap_or("stdout");
+        match output
+            .render(&entries, &opts)
+            .and_then(|buf| output.write(&buf))
+        {
+            Ok(false) => {}
+            Ok(true) => {
+                changed = true;
+                for hook in &output.on_change {
+                    if let Err(e) = hook.run() {
+                        error!("on_change hook for {}: {}", name, e);
+                        failed = true;
+                    }
+                }
+            }
+            Err(e) => {
+                error!("unable to write {}: {}", name, e);
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        return Err("some outputs could not be written or their hooks failed".into());
+    }
+    Ok(changed)
+}
+
+// Paths in a config are relative to the directory of the config.
+fn config_dir(path: &str) -> PathBuf {
+    Path::new(path)
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default()
 }
 
 trait OnErr<T, E> {
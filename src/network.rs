@@ -1,13 +1,265 @@
+use crate::error::Error;
+use crate::ipnet::{FitsInNet, InNet, ToIpv6, ToMac};
 use globset::Glob;
 use ipnetwork::IpNetwork;
-use pnet::datalink::{interfaces, NetworkInterface};
-use serde_yaml::Value;
+use log::warn;
+use pnet::datalink::{interfaces, MacAddr, NetworkInterface};
+use serde::{Deserialize, Serialize};
+use serde_yaml::{Mapping, Value};
+use sha2::{Digest, Sha256};
 use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::rc::Rc;
+use std::str::FromStr;
+
+fn new_interface(
+    name: &str,
+    index: u32,
+    mac: Option<MacAddr>,
+    ips: Vec<IpNetwork>,
+    flags: u32,
+) -> NetworkInterface {
+    NetworkInterface {
+        name: name.to_string(),
+        index,
+        mac,
+        ips,
+        flags,
+    }
+}
+
+/// Where the networks selectors choose from come from.
+pub trait NetworkProvider {
+    fn networks(&self) -> Vec<InterfaceNetwork>;
+}
+
+/// The interfaces of the running machine.
+pub struct LiveNetworks;
+
+impl NetworkProvider for LiveNetworks {
+    fn networks(&self) -> Vec<InterfaceNetwork> {
+        InterfaceNetwork::all()
+    }
+}
+
+/// A fixed list of networks, such as those declared in the config.
+pub struct StaticNetworks(pub Vec<InterfaceNetwork>);
+
+impl NetworkProvider for StaticNetworks {
+    fn networks(&self) -> Vec<InterfaceNetwork> {
+        self.0.clone()
+    }
+}
+
+// Serializable form of an interface in a snapshot.
+#[derive(Serialize, Deserialize)]
+struct InterfaceSnapshot {
+    name: String,
+    index: u32,
+    mac: Option<String>,
+    flags: u32,
+    ips: Vec<IpNetwork>,
+}
+
+impl From<&NetworkInterface> for InterfaceSnapshot {
+    fn from(iface: &NetworkInterface) -> Self {
+        Self {
+            name: iface.name.clone(),
+            index: iface.index,
+            mac: iface.mac.map(|m| m.to_string()),
+            flags: iface.flags,
+            ips: iface.ips.clone(),
+        }
+    }
+}
+
+impl InterfaceSnapshot {
+    fn to_interface(&self) -> NetworkInterface {
+        let mac = self.mac.as_ref().and_then(|m| {
+            m.parse()
+                .map_err(|_| warn!("invalid mac for {}: {}", self.name, m))
+                .ok()
+        });
+        new_interface(&self.name, self.index, mac, self.ips.clone(), self.flags)
+    }
+}
+
+/// Interfaces read from a json or yaml snapshot.
+pub struct SnapshotNetworks(Vec<NetworkInterface>);
+
+impl SnapshotNetworks {
+    /// Captures the interfaces of the running machine.
+    pub fn capture() -> Self {
+        Self(interfaces())
+    }
+
+    fn interfaces(&self) -> Vec<InterfaceSnapshot> {
+        self.0.iter().map(InterfaceSnapshot::from).collect()
+    }
+
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(&self.interfaces())
+    }
+
+    pub fn to_yaml(&self) -> Result<String, serde_yaml::Error> {
+        serde_yaml::to_string(&self.interfaces())
+    }
+
+    pub fn load(path: &str) -> io::Result<Self> {
+        let snapshot: Vec<InterfaceSnapshot> = serde_yaml::from_reader(File::open(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self(
+            snapshot
+                .iter()
+                .map(InterfaceSnapshot::to_interface)
+                .collect(),
+        ))
+    }
+}
+
+impl NetworkProvider for SnapshotNetworks {
+    fn networks(&self) -> Vec<InterfaceNetwork> {
+        InterfaceNetwork::from_interfaces(&self.0)
+    }
+}
+
+/// What to do with a generated address that wraps around the host bits of
+/// its network, or lands on the network, broadcast or interface address.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AddressPolicy {
+    Refuse,
+    Warn,
+    Allow,
+}
+
+pub const ADDRESS_POLICIES: &[&str] = &["refuse", "warn", "allow"];
+
+impl FromStr for AddressPolicy {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "refuse" => Ok(Self::Refuse),
+            "warn" => Ok(Self::Warn),
+            "allow" => Ok(Self::Allow),
+            _ => Err(format!("unknown address policy: {}", s)),
+        }
+    }
+}
+
+/// How the host bits of ipv6 addresses are made from host integers and mac
+/// addresses.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Ipv6Mode {
+    /// Modified EUI-64 of the mac address.
+    Eui64,
+    /// A hash of the prefix, the host name and a secret, as in RFC 7217.
+    StablePrivacy,
+    /// The integer in the low bits.
+    Int,
+    /// The ipv4 address of the host on the same interface in the low 32
+    /// bits.
+    Ipv4Embedded,
+}
+
+pub const IPV6_MODES: &[&str] = &["eui64", "stable-privacy", "int", "ipv4-embedded"];
+
+/// The shortest secret of stable privacy addresses, RFC 7217 asks for at
+/// least 128 bits.
+pub const MIN_SECRET_LEN: usize = 16;
+
+impl FromStr for Ipv6Mode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "eui64" => Ok(Self::Eui64),
+            "stable-privacy" => Ok(Self::StablePrivacy),
+            "int" => Ok(Self::Int),
+            "ipv4-embedded" => Ok(Self::Ipv4Embedded),
+            _ => Err(format!("unknown ipv6 mode: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Ipv6Mode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Eui64 => write!(f, "eui64"),
+            Self::StablePrivacy => write!(f, "stable-privacy"),
+            Self::Int => write!(f, "int"),
+            Self::Ipv4Embedded => write!(f, "ipv4-embedded"),
+        }
+    }
+}
+
+/// Options of a selector changing how host integers become addresses in the
+/// networks it matched. `offset` is added to the integer, except for EUI-64
+/// addresses which follow the mac address of the host, and a base is an
+/// address whose host bits the integer is added to, in place of deriving
+/// the address from a mac address. `ipv6_mode` replaces the mode given on
+/// the command line.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Numbering {
+    pub offset: Option<u64>,
+    pub ipv4_base: Option<Ipv4Addr>,
+    pub ipv6_base: Option<Ipv6Addr>,
+    pub ipv6_mode: Option<Ipv6Mode>,
+}
+
+const NUMBERING_KEYS: &[&str] = &["offset", "ipv4_base", "ipv6_base", "ipv6_mode"];
+
+impl Numbering {
+    fn is_option(key: &Value) -> bool {
+        key.as_str()
+            .filter(|k| NUMBERING_KEYS.contains(&k.to_lowercase().as_ref()))
+            .is_some()
+    }
+
+    /// Reads the options in a selector mapping, or fails with the first
+    /// invalid one.
+    fn from_map(map: &Mapping) -> Result<Self, Error> {
+        let mut numbering = Self::default();
+        for (k, v) in map.iter().filter(|(k, _)| Self::is_option(k)) {
+            let invalid = || Error::InvalidSelector(v.clone());
+            let s = v.as_str();
+            match k.as_str().unwrap_or_default().to_lowercase().as_ref() {
+                "offset" => {
+                    numbering.offset =
+                        Some(v.as_u64().or_else(|| s?.parse().ok()).ok_or_else(invalid)?)
+                }
+                "ipv4_base" => {
+                    numbering.ipv4_base = Some(s.and_then(|s| s.parse().ok()).ok_or_else(invalid)?)
+                }
+                "ipv6_base" => {
+                    numbering.ipv6_base = Some(s.and_then(|s| s.parse().ok()).ok_or_else(invalid)?)
+                }
+                _ => {
+                    numbering.ipv6_mode = Some(s.and_then(|s| s.parse().ok()).ok_or_else(invalid)?)
+                }
+            }
+        }
+        Ok(numbering)
+    }
+
+    /// Sets the options given in `other`, keeping the others.
+    fn merge(&mut self, other: &Self) {
+        self.offset = other.offset.or(self.offset);
+        self.ipv4_base = other.ipv4_base.or(self.ipv4_base);
+        self.ipv6_base = other.ipv6_base.or(self.ipv6_base);
+        self.ipv6_mode = other.ipv6_mode.or(self.ipv6_mode);
+    }
+}
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct InterfaceNetwork {
     pub iface: Option<NetworkInterface>,
     pub network: IpNetwork,
+    pub policy: AddressPolicy,
+    pub numbering: Numbering,
+    /// Secret key of stable privacy addresses.
+    pub secret: Option<Rc<[u8]>>,
 }
 
 impl InterfaceNetwork {
@@ -15,6 +267,9 @@ impl InterfaceNetwork {
         Self {
             iface: Some(iface),
             network,
+            policy: AddressPolicy::Warn,
+            numbering: Numbering::default(),
+            secret: None,
         }
     }
 
@@ -22,6 +277,9 @@ impl InterfaceNetwork {
         Self {
             iface: None,
             network,
+            policy: AddressPolicy::Warn,
+            numbering: Numbering::default(),
+            secret: None,
         }
     }
     fn none_v4() -> Self {
@@ -32,8 +290,30 @@ impl InterfaceNetwork {
         Self::new_net_only("::/0".parse().unwrap())
     }
 
-    fn all() -> Vec<Self> {
-        interfaces()
+    /// The networks of null selectors, which stand for no network in
+    /// particular. Adding them to the networks selected from lets them
+    /// carry a policy and options like any other network, only null
+    /// selectors select them.
+    pub fn none_networks() -> Vec<Self> {
+        vec![Self::none_v4(), Self::none_v6()]
+    }
+
+    pub fn is_none_network(&self) -> bool {
+        self.iface.is_none() && self.network.prefix() == 0
+    }
+
+    // The network address of the interface network, without the host bits
+    // of the interface address.
+    pub fn cidr(&self) -> IpNetwork {
+        IpNetwork::new(self.network.network(), self.network.prefix()).unwrap_or(self.network)
+    }
+
+    pub fn all() -> Vec<Self> {
+        Self::from_interfaces(&interfaces())
+    }
+
+    fn from_interfaces(ifaces: &[NetworkInterface]) -> Vec<Self> {
+        ifaces
             .iter()
             .map(|i| {
                 i.ips
@@ -44,11 +324,308 @@ impl InterfaceNetwork {
             .collect()
     }
 
+    /// Networks of virtual interfaces declared in the `networks` section of
+    /// the config. Each interface lists its addresses with their prefix, and
+    /// optionally a mac address and an integer index.
+    pub fn from_val(val: &Value) -> Vec<Self> {
+        match val.as_mapping() {
+            Some(map) => Self::from_interfaces(&Self::virtual_interfaces(map)),
+            None => {
+                warn!("invalid networks: {:?}", val);
+                Vec::new()
+            }
+        }
+    }
+
+    fn virtual_interfaces(map: &Mapping) -> Vec<NetworkInterface> {
+        map.iter()
+            .filter_map(|(k, v)| {
+                let name = match k.as_str() {
+                    Some(name) => name,
+                    None => {
+                        warn!("invalid interface name: {:?}", k);
+                        return None;
+                    }
+                };
+                let vals = match v {
+                    Value::Sequence(seq) => seq.iter().collect(),
+                    _ => vec![v],
+                };
+                let mut index = 0;
+                let mut mac = None;
+                let mut ips = Vec::new();
+                for v in vals {
+                    if let Some(i) = v.as_u64().and_then(|i| u32::try_from(i).ok()) {
+                        index = i;
+                    } else if let Some(m) = v.as_str().and_then(|s| s.parse::<MacAddr>().ok()) {
+                        mac = Some(m);
+                    } else if let Some(ip) = v.as_str().and_then(|s| s.parse::<IpNetwork>().ok()) {
+                        ips.push(ip);
+                    } else {
+                        warn!("invalid network for {}: {:?}", name, v);
+                    }
+                }
+                Some(new_interface(name, index, mac, ips, 0))
+            })
+            .collect()
+    }
+
+    /// The address of host integer `i` in the network, following the
+    /// numbering options of the selector that matched it.
+    pub fn host_address(&self, i: u64) -> IpAddr {
+        let n = i.wrapping_add(self.numbering.offset.unwrap_or(0));
+        match (self.network, self.numbering) {
+            (
+                IpNetwork::V4(net),
+                Numbering {
+                    ipv4_base: Some(base),
+                    ..
+                },
+            ) => IpAddr::V4(Ipv4Addr::from(u32::from(base).wrapping_add(n as u32)).in_net(&net)),
+            (
+                IpNetwork::V6(net),
+                Numbering {
+                    ipv6_base: Some(base),
+                    ..
+                },
+            ) => IpAddr::V6(Ipv6Addr::from(u128::from(base).wrapping_add(n.into())).in_net(&net)),
+            (
+                IpNetwork::V6(net),
+                Numbering {
+                    ipv6_mode: Some(Ipv6Mode::Int),
+                    ..
+                },
+            ) => IpAddr::V6(n.to_ipv6().in_net(&net)),
+            // EUI-64 addresses follow the mac address of the host, which the
+            // offset does not change
+            (IpNetwork::V6(_), _) => i.to_mac().in_net(&self.network),
+            _ => n.to_mac().in_net(&self.network),
+        }
+    }
+
+    /// Whether ipv6 addresses in the network embed the ipv4 address of the
+    /// host on the same interface.
+    pub fn ipv4_embedded(&self) -> bool {
+        self.network.is_ipv6() && self.numbering.ipv6_mode == Some(Ipv6Mode::Ipv4Embedded)
+    }
+
+    /// The `ipv4-embedded` address of a host with the ipv4 address `ip` on
+    /// the same interface, which is in the low 32 bits. `None` in ipv4
+    /// networks.
+    pub fn embedded_address(&self, ip: Ipv4Addr) -> Option<IpAddr> {
+        match self.network {
+            IpNetwork::V6(net) => Some(IpAddr::V6(ip.in_net(&net))),
+            IpNetwork::V4(_) => None,
+        }
+    }
+
+    /// Whether `host_address` places host integer `i` in the host bits of
+    /// the network without wrapping around.
+    pub fn host_fits(&self, i: u64) -> bool {
+        let n = match i.checked_add(self.numbering.offset.unwrap_or(0)) {
+            Some(n) => n,
+            None => return false,
+        };
+        match (self.network, self.numbering) {
+            (
+                IpNetwork::V4(net),
+                Numbering {
+                    ipv4_base: Some(base),
+                    ..
+                },
+            ) => {
+                let host_mask = u64::from(!u32::from(net.mask()));
+                (u64::from(u32::from(base)) & host_mask) + n <= host_mask
+            }
+            (
+                IpNetwork::V6(net),
+                Numbering {
+                    ipv6_base: Some(base),
+                    ..
+                },
+            ) => {
+                let host_mask = !u128::from(net.mask());
+                (u128::from(base) & host_mask) + u128::from(n) <= host_mask
+            }
+            (
+                IpNetwork::V6(net),
+                Numbering {
+                    ipv6_mode: Some(Ipv6Mode::Int),
+                    ..
+                },
+            ) => u128::from(n) & u128::from(net.mask()) == 0,
+            (IpNetwork::V6(_), _) => i.fits_in_net(&self.network),
+            _ => n.fits_in_net(&self.network),
+        }
+    }
+
+    /// Whether ipv6 addresses in the network are stable privacy addresses
+    /// rather than derived from host integers and mac addresses.
+    pub fn stable_privacy(&self) -> bool {
+        self.network.is_ipv6() && self.numbering.ipv6_mode == Some(Ipv6Mode::StablePrivacy)
+    }
+
+    /// The RFC 7217 address of `host` in the network. Its host bits are
+    /// taken from a SHA-256 hash of the prefix, the host name, a counter and
+    /// the secret, so it is the same on every run but tells nothing about
+    /// the mac address. The counter is bumped while the address is
+    /// reserved. `None` in ipv4 networks and without a secret.
+    pub fn stable_privacy_address(&self, host: &str) -> Option<IpAddr> {
+        let net = match self.network {
+            IpNetwork::V6(net) => net,
+            IpNetwork::V4(_) => return None,
+        };
+        let secret = match &self.secret {
+            Some(secret) => secret,
+            None => {
+                warn!(
+                    "no secret for the stable privacy address of {} on {}",
+                    host,
+                    self.cidr()
+                );
+                return None;
+            }
+        };
+        (0..=u8::MAX)
+            .map(|dad_counter| {
+                let hash = Sha256::new()
+                    .chain(net.network().octets())
+                    .chain(host.as_bytes())
+                    .chain([dad_counter])
+                    .chain(&secret[..])
+                    .finalize();
+                let mut id = [0; 16];
+                id.copy_from_slice(&hash[16..]);
+                IpAddr::V6(Ipv6Addr::from(id).in_net(&net))
+            })
+            .find(|ip| self.reserved(*ip).is_none())
+    }
+
+    /// Why `ip` should not be given to a host in the network: it is the
+    /// network address, the ipv4 broadcast address or the address of the
+    /// interface itself.
+    pub fn reserved(&self, ip: IpAddr) -> Option<&'static str> {
+        let host_bits = match self.network {
+            IpNetwork::V4(_) => 32 - self.network.prefix(),
+            IpNetwork::V6(_) => 128 - self.network.prefix(),
+        };
+        // point to point and single address networks have no reserved addresses
+        if host_bits >= 2 {
+            if ip == self.network.network() {
+                return Some("is the network address");
+            }
+            if let IpNetwork::V4(v4) = self.network {
+                if ip == v4.broadcast() {
+                    return Some("is the broadcast address");
+                }
+            }
+        }
+        if self.iface.is_some() && ip == self.network.ip() {
+            return Some("is the address of the interface");
+        }
+        None
+    }
+
+    /// Sets the address policy of the networks matched by each selector of
+    /// the `address_policy` section of the config.
+    pub fn with_policies(mut networks: Vec<Self>, val: &Value) -> Vec<Self> {
+        let map = match val.as_mapping() {
+            Some(map) => map,
+            None => {
+                warn!("invalid address policies: {:?}", val);
+                return networks;
+            }
+        };
+        for (selector, policy) in map {
+            let policy = match policy.as_str().map(str::parse::<AddressPolicy>) {
+                Some(Ok(policy)) => policy,
+                Some(Err(e)) => {
+                    warn!("{}", e);
+                    continue;
+                }
+                None => {
+                    warn!("invalid address policy: {:?}", policy);
+                    continue;
+                }
+            };
+            let matched = Self::filter_networks(&networks, selector);
+            for n in networks.iter_mut().filter(|n| matched.contains(n)) {
+                n.policy = policy;
+            }
+        }
+        networks
+    }
+
+    /// Live networks, with interfaces declared in the config replacing live
+    /// interfaces of the same name.
+    pub fn merged(live: Vec<Self>, declared: Vec<Self>) -> Vec<Self> {
+        let names: Vec<String> = declared
+            .iter()
+            .filter_map(|n| n.iface.as_ref().map(|i| i.name.clone()))
+            .collect();
+        live.into_iter()
+            .filter(|n| {
+                n.iface
+                    .as_ref()
+                    .filter(|i| names.contains(&i.name))
+                    .is_none()
+            })
+            .chain(declared)
+            .collect()
+    }
+
     pub fn filtered(selector: &Value) -> Vec<Self> {
         Self::filter_networks(&Self::all(), selector)
     }
 
-    fn filter_networks(networks: &[Self], selector: &Value) -> Vec<Self> {
+    /// Fails for selectors `filter_networks` does not understand, which
+    /// select nothing.
+    pub fn check_selector(selector: &Value) -> Result<(), Error> {
+        match selector {
+            Value::Sequence(seq) => seq.iter().try_for_each(Self::check_selector),
+            Value::Mapping(map) => {
+                Numbering::from_map(map)?;
+                map.iter()
+                    .filter(|(k, _)| !Numbering::is_option(k))
+                    .try_for_each(|(selector, filter)| {
+                        Self::check_selector(selector)?;
+                        Self::check_selector(filter)
+                    })
+            }
+            Value::Null | Value::String(_) => Ok(()),
+            _ if selector
+                .as_u64()
+                .and_then(|x| u32::try_from(x).ok())
+                .is_some() =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::InvalidSelector(selector.clone())),
+        }
+    }
+
+    pub fn filter_networks(networks: &[Self], selector: &Value) -> Vec<Self> {
+        if selector.is_null() {
+            let none: Vec<Self> = networks
+                .iter()
+                .filter(|n| n.is_none_network())
+                .cloned()
+                .collect();
+            if none.is_empty() {
+                return Self::none_networks();
+            }
+            return none;
+        }
+
+        if networks.iter().any(Self::is_none_network) {
+            let networks: Vec<Self> = networks
+                .iter()
+                .filter(|n| !n.is_none_network())
+                .cloned()
+                .collect();
+            return Self::filter_networks(&networks, selector);
+        }
+
         if let Some(seq) = selector.as_sequence() {
             return seq
                 .iter()
@@ -58,17 +635,30 @@ impl InterfaceNetwork {
         }
 
         if let Some(map) = selector.as_mapping() {
-            return map
+            let numbering = Numbering::from_map(map).unwrap_or_else(|e| {
+                warn!("{}", e);
+                Numbering::default()
+            });
+            let selectors: Vec<_> = map
                 .iter()
-                .map(|(selector, filter)| {
-                    Self::filter_networks(&Self::filter_networks(networks, selector), filter)
-                })
-                .flatten()
+                .filter(|(k, _)| !Numbering::is_option(k))
                 .collect();
-        }
-
-        if selector.is_null() {
-            return vec![Self::none_v4(), Self::none_v6()];
+            // a mapping of only options applies them to every network
+            let mut selected: Vec<Self> = if selectors.is_empty() {
+                networks.to_vec()
+            } else {
+                selectors
+                    .into_iter()
+                    .map(|(selector, filter)| {
+                        Self::filter_networks(&Self::filter_networks(networks, selector), filter)
+                    })
+                    .flatten()
+                    .collect()
+            };
+            for n in &mut selected {
+                n.numbering.merge(&numbering);
+            }
+            return selected;
         }
 
         if let Some(i) = selector.as_u64().and_then(|x| u32::try_from(x).ok()) {
@@ -140,3 +730,57 @@ impl InterfaceNetwork {
         Vec::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SNAPSHOT_YAML: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/snapshot.yaml");
+    const SNAPSHOT_JSON: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/data/snapshot.json");
+
+    #[test]
+    fn snapshot_yaml_round_trips() {
+        let snapshot = SnapshotNetworks::load(SNAPSHOT_YAML).unwrap();
+        assert_eq!(
+            snapshot.to_yaml().unwrap().trim_end(),
+            std::fs::read_to_string(SNAPSHOT_YAML).unwrap().trim_end()
+        );
+    }
+
+    #[test]
+    fn snapshot_json_round_trips() {
+        let snapshot = SnapshotNetworks::load(SNAPSHOT_JSON).unwrap();
+        assert_eq!(
+            snapshot.to_json().unwrap().trim_end(),
+            std::fs::read_to_string(SNAPSHOT_JSON).unwrap().trim_end()
+        );
+    }
+
+    #[test]
+    fn snapshot_formats_agree() {
+        let yaml = SnapshotNetworks::load(SNAPSHOT_YAML).unwrap().networks();
+        let json = SnapshotNetworks::load(SNAPSHOT_JSON).unwrap().networks();
+        assert_eq!(yaml, json);
+        let cidrs: Vec<_> = yaml.iter().map(|n| n.cidr().to_string()).collect();
+        assert_eq!(
+            cidrs,
+            [
+                "127.0.0.0/8",
+                "::1/128",
+                "192.168.1.0/24",
+                "2001:db8::/64",
+                "10.9.0.0/24"
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_keeps_interfaces_without_mac() {
+        let networks = SnapshotNetworks::load(SNAPSHOT_YAML).unwrap().networks();
+        let wg0 = networks
+            .iter()
+            .find(|n| n.iface.as_ref().map(|i| i.name.as_str()) == Some("wg0"))
+            .unwrap();
+        assert_eq!(wg0.iface.as_ref().unwrap().mac, None);
+    }
+}
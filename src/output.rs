@@ -0,0 +1,252 @@
+use crate::entry::{Entry, EntryIterator, HostsOptions, UnboundOptions};
+use crate::hook::Hook;
+use crate::template::Template;
+use crate::zone::ZoneHeader;
+use log::{info, warn};
+use serde_yaml::Value;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+pub const FORMATS: &[&str] = &[
+    "dnsmasq", "zone", "ptr", "hosts", "unbound", "dhcpd", "kea", "json", "yaml", "template",
+    "env", "ip-token",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Dnsmasq,
+    Zone,
+    Ptr,
+    Hosts,
+    Unbound,
+    Dhcpd,
+    Kea,
+    Json,
+    Yaml,
+    Template,
+    Env,
+    IpToken,
+}
+
+impl FromStr for Format {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "dnsmasq" => Ok(Self::Dnsmasq),
+            "zone" => Ok(Self::Zone),
+            "ptr" => Ok(Self::Ptr),
+            "hosts" => Ok(Self::Hosts),
+            "unbound" => Ok(Self::Unbound),
+            "dhcpd" => Ok(Self::Dhcpd),
+            "kea" => Ok(Self::Kea),
+            "json" => Ok(Self::Json),
+            "yaml" => Ok(Self::Yaml),
+            "template" => Ok(Self::Template),
+            "env" => Ok(Self::Env),
+            "ip-token" => Ok(Self::IpToken),
+            _ => Err(format!("unknown format: {}", s)),
+        }
+    }
+}
+
+// Settings shared by every output of a run.
+#[derive(Clone, Default)]
+pub struct OutputOptions {
+    pub domain: Option<String>,
+    pub zone: ZoneHeader,
+    pub hosts_group: bool,
+    pub hosts_localhost: bool,
+    pub unbound_local_zone: Option<String>,
+    // device of the hosts in ip-token output
+    pub token_dev: Option<String>,
+    // used by template outputs without a template of their own
+    pub template: Option<Template>,
+}
+
+#[derive(Clone)]
+pub struct Output {
+    pub format: Format,
+    // stdout when not set
+    pub path: Option<String>,
+    pub template: Option<Template>,
+    // run when the content of the output changed
+    pub on_change: Vec<Hook>,
+}
+
+impl FromStr for Output {
+    type Err = String;
+    // `format=path`, or just `format` to write to stdout
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '=');
+        let format = parts.next().unwrap_or_default().parse()?;
+        Ok(Self::new(format, parts.next().map(String::from)))
+    }
+}
+
+impl Output {
+    pub fn new(format: Format, path: Option<String>) -> Self {
+        Self {
+            format,
+            path,
+            template: None,
+            on_change: Vec::new(),
+        }
+    }
+
+    /// Reads the outputs section of a config. Template paths are relative to
+    /// `dir`, the directory of the config.
+    pub fn outputs_from_val(val: &Value, dir: &Path) -> Vec<Self> {
+        match val {
+            Value::Sequence(seq) => seq.iter().filter_map(|v| Self::from_val(v, dir)).collect(),
+            _ => Self::from_val(val, dir).into_iter().collect(),
+        }
+    }
+
+    /// The template files named in the outputs section of a config, relative
+    /// to `dir` like in `outputs_from_val`.
+    pub fn template_paths(val: &Value, dir: &Path) -> Vec<PathBuf> {
+        let outputs = match val {
+            Value::Sequence(seq) => seq.iter().collect(),
+            _ => vec![val],
+        };
+        outputs
+            .into_iter()
+            .filter_map(|v| v.get("template")?.as_str())
+            .map(|template| dir.join(template))
+            .collect()
+    }
+
+    fn from_val(val: &Value, dir: &Path) -> Option<Self> {
+        let format = match val.get("format").and_then(Value::as_str).map(str::parse) {
+            Some(Ok(format)) => format,
+            Some(Err(e)) => {
+                warn!("{}", e);
+                return None;
+            }
+            None => {
+                warn!("output requires a format: {:?}", val);
+                return None;
+            }
+        };
+        let path = val.get("path").and_then(Value::as_str).map(String::from);
+        let mut output = Self::new(format, path);
+        if let Some(template) = val.get("template").and_then(Value::as_str) {
+            output.template = Some(Template::load(&dir.join(template).to_string_lossy())?);
+        }
+        if let Some(hooks) = val.get("on_change") {
+            output.on_change = Hook::hooks_from_val(hooks);
+        }
+        Some(output)
+    }
+
+    pub fn render(&self, entries: &[Entry], opts: &OutputOptions) -> io::Result<Vec<u8>> {
+        let mut zone = opts.zone.clone();
+        if self.format == Format::Zone {
+            let previous = self.path.as_ref().and_then(|p| fs::read(p).ok());
+            zone.update_serial(previous.as_deref(), |zone| {
+                self.render_zone(entries, opts, zone)
+            })?;
+        }
+        self.render_zone(entries, opts, &zone)
+    }
+
+    fn render_zone(
+        &self,
+        entries: &[Entry],
+        opts: &OutputOptions,
+        zone: &ZoneHeader,
+    ) -> io::Result<Vec<u8>> {
+        let domain = opts.domain.clone();
+        if matches!(self.format, Format::Ptr | Format::Unbound) && !has_domain(&domain) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} output requires --domain or a zone origin",
+                    format!("{:?}", self.format).to_lowercase()
+                ),
+            ));
+        }
+        let entries = entries.iter().cloned();
+        let entries = match self.format {
+            Format::Dnsmasq => entries.as_dnsmasq_reservations(),
+            Format::Zone => entries.as_zone_file(zone.clone()),
+            Format::Ptr => entries.as_ptr_records(domain),
+            Format::Hosts => entries.as_hosts_file(HostsOptions {
+                domain,
+                group: opts.hosts_group,
+                localhost: opts.hosts_localhost,
+            }),
+            Format::Unbound => entries.as_unbound_local_data(UnboundOptions {
+                domain,
+                local_zone: opts.unbound_local_zone.clone(),
+            }),
+            Format::Dhcpd => entries.as_dhcpd_hosts(),
+            Format::Kea => entries.as_kea_reservations(),
+            Format::Json => entries.as_json(),
+            Format::Yaml => entries.as_yaml(),
+            Format::Template => match self.template.as_ref().or(opts.template.as_ref()) {
+                Some(template) => entries.as_templated(template.clone(), domain),
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "template output requires a template",
+                    ))
+                }
+            },
+            Format::Env => entries.as_env_vars(),
+            Format::IpToken => entries.as_ip_tokens(opts.token_dev.clone()),
+        };
+        let mut buf = Vec::new();
+        entries.write(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Writes rendered content to the output and returns whether it changed.
+    /// Files are replaced atomically through a temporary file in the same
+    /// directory, and left untouched when the content is identical.
+    pub fn write(&self, buf: &[u8]) -> io::Result<bool> {
+        let path = match &self.path {
+            Some(path) => Path::new(path),
+            None => {
+                io::stdout().write_all(buf)?;
+                return Ok(true);
+            }
+        };
+        if fs::read(path).ok().as_deref() == Some(buf) {
+            info!("{} unchanged", path.display());
+            return Ok(false);
+        }
+
+        let file_name = path
+            .file_name()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file path"))?;
+        let tmp = path.with_file_name(format!(
+            ".{}.{}.tmp",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        let res = (|| {
+            let mut f = fs::File::create(&tmp)?;
+            f.write_all(buf)?;
+            f.sync_all()?;
+            if let Ok(meta) = fs::metadata(path) {
+                fs::set_permissions(&tmp, meta.permissions())?;
+            }
+            fs::rename(&tmp, path)
+        })();
+        if res.is_err() {
+            let _ = fs::remove_file(&tmp);
+        }
+        res?;
+        info!("{} updated", path.display());
+        Ok(true)
+    }
+}
+
+// A bare host name qualified with nothing but the root would be a top level
+// domain.
+fn has_domain(domain: &Option<String>) -> bool {
+    matches!(domain.as_deref().map(|d| d.trim_matches('.')), Some(d) if !d.is_empty())
+}
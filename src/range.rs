@@ -0,0 +1,175 @@
+use crate::error::Error;
+use serde_yaml::Value;
+
+// The most hosts a single range can stand for, so a typo like
+// `node[1-10000000]` is refused rather than generating them all.
+const MAX_HOSTS: u64 = 65536;
+
+/// A host name with a range of numbers in brackets, like `node[01-40]`,
+/// which stands for a host per number. Zero padding of the first number is
+/// kept in the names.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostRange {
+    prefix: String,
+    pub start: u64,
+    pub end: u64,
+    width: usize,
+    suffix: String,
+}
+
+impl HostRange {
+    /// Finds the range in `name`, `None` when it has none. Fails for ranges
+    /// running backwards or standing for more than `MAX_HOSTS` hosts.
+    pub fn parse(name: &str) -> Result<Option<Self>, Error> {
+        let open = match name.find('[') {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        let close = match name[open..].find(']') {
+            Some(i) => open + i,
+            None => return Ok(None),
+        };
+        let mut bounds = name[open + 1..close].splitn(2, '-');
+        let (first, last) = match (bounds.next(), bounds.next()) {
+            (Some(first), Some(last)) if is_number(first) && is_number(last) => (first, last),
+            _ => return Ok(None),
+        };
+        let (start, end) = match (first.parse(), last.parse()) {
+            (Ok(start), Ok(end)) if start <= end && end - start < MAX_HOSTS => (start, end),
+            _ => return Err(Error::InvalidHostName(Value::String(name.to_string()))),
+        };
+        Ok(Some(Self {
+            prefix: name[..open].to_string(),
+            start,
+            end,
+            width: if first.starts_with('0') {
+                first.len()
+            } else {
+                0
+            },
+            suffix: name[close + 1..].to_string(),
+        }))
+    }
+
+    /// The number and name of every host of the range.
+    pub fn hosts(self) -> impl Iterator<Item = (u64, String)> {
+        (self.start..=self.end).map(move |n| {
+            let name = format!(
+                "{}{:0width$}{}",
+                self.prefix,
+                n,
+                self.suffix,
+                width = self.width
+            );
+            (n, name)
+        })
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit())
+}
+
+// `BASE+` or `BASE+STEPn`, the base and step of the integer of a host in a
+// range.
+fn parse_step(s: &str) -> Option<(u64, u64)> {
+    let mut parts = s.splitn(2, '+');
+    let base = parts.next()?.trim().parse().ok()?;
+    let step = match parts.next()?.trim() {
+        "" | "n" => 1,
+        step => step.strip_suffix('n')?.trim().parse().ok()?,
+    };
+    Some((base, step))
+}
+
+/// The options of the host with number `n` of a range. Options like `100+`
+/// become the integer `100 + n`, and `100+2n` becomes `100 + 2n`, wherever
+/// they are in the options, the rest is kept as is.
+pub fn substitute(val: &Value, n: u64) -> Value {
+    match val {
+        Value::Sequence(seq) => Value::Sequence(seq.iter().map(|v| substitute(v, n)).collect()),
+        Value::Mapping(map) => Value::Mapping(
+            map.iter()
+                .map(|(k, v)| (k.clone(), substitute(v, n)))
+                .collect(),
+        ),
+        Value::String(s) => parse_step(s)
+            .and_then(|(base, step)| step.checked_mul(n)?.checked_add(base))
+            .map(|i| Value::Number(i.into()))
+            .unwrap_or_else(|| val.clone()),
+        _ => val.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(range: HostRange) -> Vec<(u64, String)> {
+        range.hosts().collect()
+    }
+
+    #[test]
+    fn names_without_a_range() {
+        assert_eq!(HostRange::parse("server1").unwrap(), None);
+        assert_eq!(HostRange::parse("node[a-c]").unwrap(), None);
+        assert_eq!(HostRange::parse("node[1-").unwrap(), None);
+        assert_eq!(HostRange::parse("node[-3]").unwrap(), None);
+    }
+
+    #[test]
+    fn range_hosts() {
+        let range = HostRange::parse("node[1-3].lab").unwrap().unwrap();
+        assert_eq!((range.start, range.end), (1, 3));
+        assert_eq!(
+            names(range),
+            [
+                (1, "node1.lab".to_string()),
+                (2, "node2.lab".to_string()),
+                (3, "node3.lab".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn range_keeps_zero_padding() {
+        let range = HostRange::parse("node[08-10]").unwrap().unwrap();
+        let hosts: Vec<_> = names(range).into_iter().map(|(_, name)| name).collect();
+        assert_eq!(hosts, ["node08", "node09", "node10"]);
+    }
+
+    #[test]
+    fn invalid_ranges() {
+        assert!(HostRange::parse("node[3-1]").is_err());
+        assert!(HostRange::parse("node[0-65536]").is_err());
+        assert!(HostRange::parse("node[1-65536]").unwrap().is_some());
+        assert!(HostRange::parse("node[1-99999999999999999999]").is_err());
+    }
+
+    fn yaml(s: &str) -> Value {
+        serde_yaml::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn substitute_steps() {
+        assert_eq!(substitute(&yaml("100+"), 3), yaml("103"));
+        assert_eq!(substitute(&yaml("100+n"), 3), yaml("103"));
+        assert_eq!(substitute(&yaml("100+2n"), 3), yaml("106"));
+        assert_eq!(substitute(&yaml("100 + 2n"), 3), yaml("106"));
+    }
+
+    #[test]
+    fn substitute_nested_options() {
+        assert_eq!(
+            substitute(&yaml("[\"02:00:00:00:00:01\", 10+, {ip6: 20+}]"), 2),
+            yaml("[\"02:00:00:00:00:01\", 12, {ip6: 22}]")
+        );
+    }
+
+    #[test]
+    fn substitute_leaves_other_values() {
+        for s in &["10", "server1", "10.0.0.1", "100+x", "+n", "~"] {
+            assert_eq!(substitute(&yaml(s), 3), yaml(s));
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use crate::entry::{Entry, Source};
+use crate::ipnet::ToReverseName;
+use log::warn;
+use serde_yaml::Value;
+use std::fs::File;
+
+#[derive(Clone)]
+pub struct Template {
+    pub header: Option<String>,
+    pub entry: String,
+    pub footer: Option<String>,
+}
+
+impl Template {
+    pub fn new(entry: &str) -> Self {
+        Self {
+            header: None,
+            entry: entry.to_string(),
+            footer: None,
+        }
+    }
+
+    pub fn load(path: &str) -> Option<Self> {
+        let f = File::open(path)
+            .map_err(|e| warn!("unable to read template {}: {}", path, e))
+            .ok()?;
+        let data: Value = serde_yaml::from_reader(f)
+            .map_err(|e| warn!("unable to parse yaml in template {}: {}", path, e))
+            .ok()?;
+        Self::from_val(&data)
+    }
+
+    pub fn from_val(val: &Value) -> Option<Self> {
+        // block scalars keep their final newline, each part is written as a line
+        let part = |k: &str| {
+            val.get(k)
+                .and_then(Value::as_str)
+                .map(|s| s.strip_suffix('\n').unwrap_or(s).to_string())
+        };
+        let mut template = match part("entry") {
+            Some(entry) => Self::new(&entry),
+            None => {
+                warn!("template requires an entry: {:?}", val);
+                return None;
+            }
+        };
+        template.header = part("header");
+        template.footer = part("footer");
+        Some(template)
+    }
+
+    pub fn render(&self, e: &Entry, domain: Option<&str>) -> String {
+        let mut out = String::new();
+        let mut rest = self.entry.as_str();
+        while let Some(start) = rest.find("{{") {
+            out.push_str(&rest[..start]);
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            out.push_str(&expand(&rest[start + 2..end], e, domain));
+            rest = &rest[end + 2..];
+        }
+        out.push_str(rest);
+        out
+    }
+}
+
+fn expand(expr: &str, e: &Entry, domain: Option<&str>) -> String {
+    let mut parts = split_filters(expr).into_iter().map(str::trim);
+    let var = parts.next().unwrap_or_default();
+    let mut val = match var {
+        "name" => e.name.clone(),
+        "fqdn" => e.fqdn(domain),
+        "ip" => e.ip.to_string(),
+        "mac" => e.mac.map(|m| m.to_string()).unwrap_or_default(),
+        "family" => if e.ip.is_ipv4() { "ipv4" } else { "ipv6" }.to_string(),
+        "iface" => e
+            .network
+            .as_ref()
+            .and_then(|n| n.iface.as_ref())
+            .map(|i| i.name.clone())
+            .unwrap_or_default(),
+        "network" => e
+            .network
+            .as_ref()
+            .map(|n| n.cidr().to_string())
+            .unwrap_or_default(),
+        "reverse" => e.ip.to_reverse_name(),
+        "source" => match &e.source {
+            Some(Source::Config(f)) | Some(Source::Leases(f)) => f.clone(),
+            None => String::new(),
+        },
+        _ => {
+            warn!("unknown template variable: {}", var);
+            return format!("{{{{{}}}}}", expr);
+        }
+    };
+    for filter in parts {
+        val = apply_filter(filter, val);
+    }
+    val
+}
+
+// Filters are written as `name` or `name("arg", ...)`.
+fn apply_filter(filter: &str, val: String) -> String {
+    let (name, args) = match filter.find('(') {
+        Some(i) => (filter[..i].trim(), quoted_args(&filter[i..])),
+        None => (filter, Vec::new()),
+    };
+    match (name, args.as_slice()) {
+        ("upper", []) => val.to_uppercase(),
+        ("lower", []) => val.to_lowercase(),
+        ("replace", [from, to]) => val.replace(from.as_str(), to),
+        ("trim_domain", []) => val.split('.').next().unwrap_or_default().to_string(),
+        ("trim_domain", [domain]) => val
+            .strip_suffix(&format!(".{}", domain.trim_matches('.')))
+            .map(String::from)
+            .unwrap_or(val),
+        _ => {
+            warn!("unknown template filter: {}", filter);
+            val
+        }
+    }
+}
+
+// Splits on the `|` between filters, leaving the ones inside quoted
+// arguments alone.
+fn split_filters(expr: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut start, mut quoted, mut escaped) = (0, false, false);
+    for (i, c) in expr.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            '|' if !quoted => {
+                parts.push(&expr[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    parts.push(&expr[start..]);
+    parts
+}
+
+// The quoted arguments of a filter, `\"` and `\\` stand for a quote and a
+// backslash.
+fn quoted_args(s: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut arg: Option<String> = None;
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match (&mut arg, c) {
+            (None, '"') => arg = Some(String::new()),
+            (None, _) => (),
+            (Some(_), '"') => args.extend(arg.take()),
+            (Some(a), '\\') => a.extend(chars.next()),
+            (Some(a), c) => a.push(c),
+        }
+    }
+    args
+}
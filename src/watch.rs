@@ -0,0 +1,140 @@
+use log::{debug, warn};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const FILE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Calls `f` whenever an interface address is added or removed, or one of
+/// `files` changes on disk. Events arriving within `debounce` of each other
+/// cause a single call. Only returns if watching could not be started or
+/// address notifications can no longer be read.
+pub fn watch<F: FnMut()>(files: Vec<PathBuf>, debounce: Duration, mut f: F) -> io::Result<()> {
+    let (tx, rx) = channel();
+    watch_addresses(tx.clone())?;
+    watch_files(files, tx);
+
+    while let Ok(event) = rx.recv() {
+        event?;
+        loop {
+            match rx.recv_timeout(debounce) {
+                Ok(event) => event?,
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+        f();
+    }
+    Ok(())
+}
+
+fn watch_files(files: Vec<PathBuf>, tx: Sender<io::Result<()>>) {
+    let mtime = |p: &PathBuf| fs::metadata(p).and_then(|m| m.modified()).ok();
+    let mut mtimes: Vec<Option<SystemTime>> = files.iter().map(mtime).collect();
+    thread::spawn(move || loop {
+        thread::sleep(FILE_POLL_INTERVAL);
+        for (path, last) in files.iter().zip(mtimes.iter_mut()) {
+            let current = mtime(path);
+            if current != *last {
+                debug!("{} changed", path.display());
+                *last = current;
+                if tx.send(Ok(())).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+}
+
+#[cfg(target_os = "linux")]
+fn watch_addresses(tx: Sender<io::Result<()>>) -> io::Result<()> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    // subscribe to RTM_NEWADDR and RTM_DELADDR notifications
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            libc::NETLINK_ROUTE,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let mut sock = unsafe { fs::File::from_raw_fd(fd) };
+    let mut addr: libc::sockaddr_nl = unsafe { std::mem::zeroed() };
+    addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+    addr.nl_groups = (libc::RTMGRP_IPV4_IFADDR | libc::RTMGRP_IPV6_IFADDR) as u32;
+    let res = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    thread::spawn(move || {
+        let mut buf = vec![0u8; 16384];
+        loop {
+            let n = match sock.read(&mut buf) {
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                // the kernel dropped notifications, the addresses may have
+                // changed in the meantime
+                Err(e) if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                    warn!("address notifications were lost, regenerating");
+                    if tx.send(Ok(())).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+                Err(e) => {
+                    let e = io::Error::new(
+                        e.kind(),
+                        format!("unable to read address notifications: {}", e),
+                    );
+                    let _ = tx.send(Err(e));
+                    return;
+                }
+            };
+            if address_changed(&buf[..n]) && tx.send(Ok(())).is_err() {
+                return;
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watch_addresses(_tx: Sender<io::Result<()>>) -> io::Result<()> {
+    warn!("address notifications are only supported on linux, watching files only");
+    Ok(())
+}
+
+// Walks the netlink messages in a datagram looking for address changes.
+#[cfg(target_os = "linux")]
+fn address_changed(mut buf: &[u8]) -> bool {
+    let mut changed = false;
+    while buf.len() >= 16 {
+        let len = u32::from_ne_bytes([buf[0], buf[1], buf[2], buf[3]]) as usize;
+        let msg_type = u16::from_ne_bytes([buf[4], buf[5]]);
+        if msg_type == libc::RTM_NEWADDR || msg_type == libc::RTM_DELADDR {
+            debug!("address notification: {}", msg_type);
+            changed = true;
+        }
+        // messages are aligned to 4 bytes
+        let len = (len + 3) & !3;
+        if len < 16 || len > buf.len() {
+            break;
+        }
+        buf = &buf[len..];
+    }
+    changed
+}
@@ -0,0 +1,330 @@
+use log::warn;
+use serde_yaml::Value;
+use std::convert::TryFrom;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SerialScheme {
+    Fixed,
+    Increment,
+    Date,
+    UnixTime,
+}
+
+impl SerialScheme {
+    pub fn first(self, serial: u32) -> u32 {
+        match self {
+            Self::Fixed | Self::Increment => serial,
+            Self::Date => today_serial(),
+            Self::UnixTime => unix_time(),
+        }
+    }
+
+    pub fn next(self, previous: u32) -> u32 {
+        match self {
+            Self::Fixed => previous,
+            Self::Increment => previous.wrapping_add(1),
+            Self::Date => today_serial().max(previous.wrapping_add(1)),
+            Self::UnixTime => unix_time().max(previous.wrapping_add(1)),
+        }
+    }
+}
+
+fn unix_time() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+// YYYYMMDD00 for the current UTC day.
+fn today_serial() -> u32 {
+    date_serial(unix_time())
+}
+
+// YYYYMMDD00 for the UTC day of a unix time.
+fn date_serial(time: u32) -> u32 {
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = time / 86400 + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + if m <= 2 { 1 } else { 0 };
+    (y * 10000 + m * 100 + d) * 100
+}
+
+// Serial of the SOA record in a previously generated zone file.
+pub fn serial_from_zone(zone: &str) -> Option<u32> {
+    zone.lines()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>())
+        .filter_map(|words| {
+            let i = words.iter().position(|w| w.eq_ignore_ascii_case("SOA"))?;
+            words.get(i + 3)?.parse().ok()
+        })
+        .next()
+}
+
+#[derive(Clone)]
+pub struct Soa {
+    pub mname: String,
+    pub rname: String,
+    pub serial: u32,
+    pub scheme: SerialScheme,
+    pub refresh: u32,
+    pub retry: u32,
+    pub expire: u32,
+    pub minimum: u32,
+}
+
+impl Soa {
+    pub fn new(mname: &str, rname: &str) -> Self {
+        Self {
+            mname: mname.to_string(),
+            rname: rname.to_string(),
+            serial: 1,
+            scheme: SerialScheme::Increment,
+            refresh: 3600,
+            retry: 900,
+            expire: 1_209_600,
+            minimum: 300,
+        }
+    }
+
+    fn from_val(val: &Value) -> Option<Self> {
+        let field = |k: &str| val.get(k).and_then(Value::as_str);
+        let (mname, rname) = match (field("mname"), field("rname")) {
+            (Some(mname), Some(rname)) => (mname, rname),
+            _ => {
+                warn!("soa requires mname and rname: {:?}", val);
+                return None;
+            }
+        };
+        let mut soa = Self::new(mname, rname);
+        if let Some(serial) = val.get("serial") {
+            match serial.as_str().map(str::to_lowercase).as_deref() {
+                Some("increment") => soa.scheme = SerialScheme::Increment,
+                Some("date") => soa.scheme = SerialScheme::Date,
+                Some("unixtime") => soa.scheme = SerialScheme::UnixTime,
+                _ => match serial.as_u64().and_then(|x| u32::try_from(x).ok()) {
+                    Some(x) => {
+                        soa.serial = x;
+                        soa.scheme = SerialScheme::Fixed;
+                    }
+                    None => warn!("invalid soa serial: {:?}", serial),
+                },
+            }
+        }
+        for (k, v) in [
+            ("refresh", &mut soa.refresh),
+            ("retry", &mut soa.retry),
+            ("expire", &mut soa.expire),
+            ("minimum", &mut soa.minimum),
+        ]
+        .iter_mut()
+        {
+            if let Some(x) = val.get(*k) {
+                match x.as_u64().and_then(|x| u32::try_from(x).ok()) {
+                    Some(x) => **v = x,
+                    None => warn!("invalid soa {}: {:?}", k, x),
+                }
+            }
+        }
+        Some(soa)
+    }
+
+    pub fn as_record(&self) -> String {
+        format!(
+            "@\tSOA\t{} {} {} {} {} {} {}",
+            self.mname,
+            rname_to_domain(&self.rname),
+            self.serial,
+            self.refresh,
+            self.retry,
+            self.expire,
+            self.minimum
+        )
+    }
+}
+
+// Allow the responsible mailbox to be written as an email address.
+fn rname_to_domain(rname: &str) -> String {
+    match rname.find('@') {
+        Some(i) => format!(
+            "{}.{}.",
+            rname[..i].replace('.', "\\."),
+            rname[i + 1..].trim_end_matches('.')
+        ),
+        None => rname.to_string(),
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct ZoneHeader {
+    pub origin: Option<String>,
+    pub ttl: Option<u32>,
+    pub soa: Option<Soa>,
+    pub ns: Vec<String>,
+}
+
+impl ZoneHeader {
+    pub fn from_val(val: &Value) -> Self {
+        let mut header = Self::default();
+        let map = match val.as_mapping() {
+            Some(map) => map,
+            None => {
+                warn!("invalid zone settings: {:?}", val);
+                return header;
+            }
+        };
+        for (k, v) in map {
+            match k.as_str().map(str::to_lowercase).as_deref() {
+                Some("origin") => match v.as_str() {
+                    Some(origin) => header.origin = Some(origin.to_string()),
+                    None => warn!("invalid zone origin: {:?}", v),
+                },
+                Some("ttl") => match v.as_u64().and_then(|x| u32::try_from(x).ok()) {
+                    Some(ttl) => header.ttl = Some(ttl),
+                    None => warn!("invalid zone ttl: {:?}", v),
+                },
+                Some("soa") => header.soa = Soa::from_val(v),
+                Some("ns") => {
+                    header.ns = match v {
+                        Value::Sequence(seq) => seq
+                            .iter()
+                            .filter_map(|ns| {
+                                ns.as_str().map(String::from).or_else(|| {
+                                    warn!("invalid ns: {:?}", ns);
+                                    None
+                                })
+                            })
+                            .collect(),
+                        Value::String(ns) => vec![ns.to_string()],
+                        _ => {
+                            warn!("invalid ns: {:?}", v);
+                            Vec::new()
+                        }
+                    }
+                }
+                _ => warn!("unknown zone setting: {:?}", k),
+            }
+        }
+        header
+    }
+
+    /// Picks the SOA serial for a zone rendered by `render`. The serial of the
+    /// previous zone file is kept when nothing else in the zone changed,
+    /// otherwise it is moved forward according to the serial scheme.
+    pub fn update_serial<F: Fn(&Self) -> io::Result<Vec<u8>>>(
+        &mut self,
+        previous: Option<&[u8]>,
+        render: F,
+    ) -> io::Result<()> {
+        let scheme = match &self.soa {
+            Some(soa) => soa.scheme,
+            None => return Ok(()),
+        };
+        let previous = previous.and_then(|p| {
+            let serial = serial_from_zone(&String::from_utf8_lossy(p))?;
+            Some((p, serial))
+        });
+        let serial = match previous {
+            Some((p, serial)) => {
+                self.set_serial(serial);
+                if render(self)? == p {
+                    serial
+                } else {
+                    scheme.next(serial)
+                }
+            }
+            None => scheme.first(self.soa.as_ref().map_or(1, |soa| soa.serial)),
+        };
+        self.set_serial(serial);
+        Ok(())
+    }
+
+    fn set_serial(&mut self, serial: u32) {
+        if let Some(soa) = self.soa.as_mut() {
+            if soa.scheme != SerialScheme::Fixed {
+                soa.serial = serial;
+            }
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        if let Some(origin) = &self.origin {
+            lines.push(format!("$ORIGIN {}.", origin.trim_end_matches('.')));
+        }
+        if let Some(ttl) = self.ttl {
+            lines.push(format!("$TTL {}", ttl));
+        }
+        if let Some(soa) = &self.soa {
+            lines.push(soa.as_record());
+        }
+        for ns in &self.ns {
+            lines.push(format!("@\tNS\t{}", ns));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn date_serial_of_unix_times() {
+        assert_eq!(date_serial(0), 1970010100);
+        assert_eq!(date_serial(951_868_799), 2000022900);
+        assert_eq!(date_serial(951_868_800), 2000030100);
+        assert_eq!(date_serial(1_709_251_199), 2024022900);
+        assert_eq!(date_serial(1_735_689_600), 2025010100);
+    }
+
+    #[test]
+    fn today_serial_is_a_date() {
+        let serial = today_serial();
+        assert_eq!(serial % 100, 0);
+        assert!(serial >= 2020010100);
+        assert_eq!(serial, date_serial(unix_time()));
+    }
+
+    #[test]
+    fn fixed_serial_stays() {
+        assert_eq!(SerialScheme::Fixed.first(7), 7);
+        assert_eq!(SerialScheme::Fixed.next(7), 7);
+    }
+
+    #[test]
+    fn increment_serial_wraps() {
+        assert_eq!(SerialScheme::Increment.first(7), 7);
+        assert_eq!(SerialScheme::Increment.next(7), 8);
+        assert_eq!(SerialScheme::Increment.next(u32::MAX), 0);
+    }
+
+    #[test]
+    fn date_serial_moves_forward() {
+        assert_eq!(SerialScheme::Date.next(2000010105), today_serial());
+        let ahead = today_serial() + 100;
+        assert_eq!(SerialScheme::Date.next(ahead), ahead + 1);
+    }
+
+    #[test]
+    fn unix_time_serial_moves_forward() {
+        let before = unix_time();
+        assert!(SerialScheme::UnixTime.next(1) >= before);
+        assert_eq!(SerialScheme::UnixTime.next(u32::MAX - 1), u32::MAX);
+    }
+
+    #[test]
+    fn serial_of_previous_zone() {
+        let zone = "$ORIGIN example.com.\n@\tSOA\tns1 admin 2024010100 3600 900 1209600 300\n";
+        assert_eq!(serial_from_zone(zone), Some(2024010100));
+        assert_eq!(serial_from_zone("server1\tA\t10.0.0.1\n"), None);
+    }
+}